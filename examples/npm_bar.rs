@@ -1,18 +1,12 @@
 extern crate pbr;
-use pbr::ProgressBar;
+use pbr::{ProgressBar, ProgressStyle};
 use std::thread;
 use std::time::Duration;
 
 fn main() {
     let count = 30;
     let mut pb = ProgressBar::new(count * 10);
-    pb.tick_format("\\|/-");
-    pb.format("|#--|");
-    pb.show_tick = true;
-    pb.show_speed = false;
-    pb.show_percent = false;
-    pb.show_counter = false;
-    pb.show_time_left = false;
+    pb.set_style(&ProgressStyle::npm());
     pb.inc();
     for _ in 0..count {
         for _ in 0..10 {
@@ -0,0 +1,244 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{Stdout, Write};
+use std::time::Duration;
+use pb::{ProgressBar, Units};
+use multi::{MultiBar, Pipe};
+use style::ProgressStyle;
+
+/// An invalid option of a `ProgressBarBuilder`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    /// The bar format does not have 5 glyphs.
+    Format(String),
+    /// The tick format is empty.
+    TickFormat,
+    /// An ASCII fallback format is empty, has the wrong number of glyphs,
+    /// or is not ASCII.
+    Ascii(String),
+    /// The buffered glyph is not a single glyph.
+    BufferedGlyph(String),
+    /// The layout has an unclosed "{", the text from there on.
+    Layout(String),
+    /// The width is zero.
+    Width,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildError::Format(ref format) => {
+                write!(f, "bar format {:?} needs 5 glyphs", format)
+            }
+            BuildError::TickFormat => write!(f, "tick format is empty"),
+            BuildError::Ascii(ref format) => write!(f, "invalid ASCII fallback {:?}", format),
+            BuildError::BufferedGlyph(ref glyph) => {
+                write!(f, "buffered glyph {:?} is not a single glyph", glyph)
+            }
+            BuildError::Layout(ref rest) => write!(f, "unclosed box in layout at {:?}", rest),
+            BuildError::Width => write!(f, "width is zero"),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+/// Configure a progress bar in one go, checking the options.
+///
+/// Unlike the setters of `ProgressBar`, invalid options are reported by
+/// `build`, and no option changes another one.
+///
+/// # Examples
+///
+/// ```no_run
+/// use pbr::{ProgressBarBuilder, Units};
+///
+/// let mut pb = ProgressBarBuilder::new(1024)
+///     .format("[#>.]")
+///     .units(Units::Bytes)
+///     .show_speed(false)
+///     .message("fetch ")
+///     .build()
+///     .unwrap();
+/// pb.add(512);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProgressBarBuilder {
+    total: u64,
+    style: ProgressStyle,
+    width: Option<usize>,
+    message: String,
+}
+
+impl ProgressBarBuilder {
+    /// Start configuring a bar with the given total and the default style.
+    pub fn new(total: u64) -> ProgressBarBuilder {
+        ProgressBarBuilder {
+            total,
+            style: ProgressStyle::default(),
+            width: None,
+            message: String::new(),
+        }
+    }
+
+    /// Start from a style, see `ProgressStyle`.
+    pub fn style(mut self, style: &ProgressStyle) -> ProgressBarBuilder {
+        self.style = style.clone();
+        self
+    }
+
+    /// Set the bar glyphs, see `ProgressBar::format`.
+    pub fn format(mut self, format: &str) -> ProgressBarBuilder {
+        self.style.format = format.to_owned();
+        self
+    }
+
+    /// Set the bar glyphs without Unicode, see `ProgressBar::ascii_format`.
+    pub fn ascii_format(mut self, format: &str) -> ProgressBarBuilder {
+        self.style.ascii_format = format.to_owned();
+        self
+    }
+
+    /// Set the spinner frames, see `ProgressBar::tick_format`. Unlike
+    /// there, this does not show the tick box.
+    pub fn tick_format(mut self, tick_format: &str) -> ProgressBarBuilder {
+        self.style.tick_format = tick_format.to_owned();
+        self
+    }
+
+    /// Set the spinner frames without Unicode, see
+    /// `ProgressBar::ascii_tick_format`.
+    pub fn ascii_tick_format(mut self, tick_format: &str) -> ProgressBarBuilder {
+        self.style.ascii_tick_format = tick_format.to_owned();
+        self
+    }
+
+    /// Set the glyph of the buffered part, see
+    /// `ProgressBar::set_buffered_glyph`.
+    pub fn buffered_glyph(mut self, glyph: &str) -> ProgressBarBuilder {
+        self.style.buffered_glyph = glyph.to_owned();
+        self
+    }
+
+    /// Set the order of the boxes, see `ProgressBar::set_layout`.
+    pub fn layout(mut self, layout: &str) -> ProgressBarBuilder {
+        self.style.layout = layout.to_owned();
+        self
+    }
+
+    /// Set the units of the counter and speed boxes.
+    pub fn units(mut self, units: Units) -> ProgressBarBuilder {
+        self.style.units = units;
+        self
+    }
+
+    /// Set the width, instead of the terminal width.
+    pub fn width(mut self, width: usize) -> ProgressBarBuilder {
+        self.width = Some(width);
+        self
+    }
+
+    /// Set the max refresh rate, see `ProgressBar::set_max_refresh_rate`.
+    pub fn max_refresh_rate(mut self, rate: Duration) -> ProgressBarBuilder {
+        self.style.max_refresh_rate = Some(rate);
+        self
+    }
+
+    /// Set the message, see `ProgressBar::message`.
+    pub fn message(mut self, message: &str) -> ProgressBarBuilder {
+        self.message = message.to_owned();
+        self
+    }
+
+    /// Show or hide the bar box, see `ProgressBar::show_bar`.
+    pub fn show_bar(mut self, show: bool) -> ProgressBarBuilder {
+        self.style.show_bar = show;
+        self
+    }
+
+    /// Show or hide the speed box, see `ProgressBar::show_speed`.
+    pub fn show_speed(mut self, show: bool) -> ProgressBarBuilder {
+        self.style.show_speed = show;
+        self
+    }
+
+    /// Show or hide the percent box, see `ProgressBar::show_percent`.
+    pub fn show_percent(mut self, show: bool) -> ProgressBarBuilder {
+        self.style.show_percent = show;
+        self
+    }
+
+    /// Show or hide the counter box, see `ProgressBar::show_counter`.
+    pub fn show_counter(mut self, show: bool) -> ProgressBarBuilder {
+        self.style.show_counter = show;
+        self
+    }
+
+    /// Show or hide the time left box, see `ProgressBar::show_time_left`.
+    pub fn show_time_left(mut self, show: bool) -> ProgressBarBuilder {
+        self.style.show_time_left = show;
+        self
+    }
+
+    /// Show or hide the spinner, see `ProgressBar::show_tick`.
+    pub fn show_tick(mut self, show: bool) -> ProgressBarBuilder {
+        self.style.show_tick = show;
+        self
+    }
+
+    /// Show or hide the message box, see `ProgressBar::show_message`.
+    pub fn show_message(mut self, show: bool) -> ProgressBarBuilder {
+        self.style.show_message = show;
+        self
+    }
+
+    /// Build a bar writing to stdout, detecting what it supports like
+    /// `ProgressBar::new`.
+    pub fn build(self) -> Result<ProgressBar<Stdout>, BuildError> {
+        self.check()?;
+        let mut pb = ProgressBar::new(self.total);
+        self.apply(&mut pb);
+        Ok(pb)
+    }
+
+    /// Build a bar writing to the given handle.
+    pub fn build_on<T: Write>(self, handle: T) -> Result<ProgressBar<T>, BuildError> {
+        self.check()?;
+        let mut pb = ProgressBar::on(handle, self.total);
+        self.apply(&mut pb);
+        Ok(pb)
+    }
+
+    /// Build a bar of the given `MultiBar`, see `MultiBar::create_bar`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{MultiBar, ProgressBarBuilder};
+    ///
+    /// let mut mb = MultiBar::new();
+    /// let p1 = ProgressBarBuilder::new(10).show_speed(false).build_in(&mut mb).unwrap();
+    /// ```
+    pub fn build_in<T: Write>(self, mb: &mut MultiBar<T>) -> Result<ProgressBar<Pipe>, BuildError> {
+        self.check()?;
+        let mut pb = mb.create_bar(self.total);
+        self.apply(&mut pb);
+        Ok(pb)
+    }
+
+    // check validates the options.
+    fn check(&self) -> Result<(), BuildError> {
+        self.style.validate()?;
+        if self.width == Some(0) {
+            return Err(BuildError::Width);
+        }
+        Ok(())
+    }
+
+    // apply configures a new bar.
+    fn apply<T: Write>(&self, pb: &mut ProgressBar<T>) {
+        pb.set_style(&self.style);
+        pb.set_width(self.width);
+        pb.message(&self.message);
+    }
+}
@@ -0,0 +1,86 @@
+use std::env;
+use tty::terminal_size;
+
+/// What the output can render, detected from the terminal and the usual
+/// environment variables, see `Capabilities::detect`.
+///
+/// Bars created with `ProgressBar::new` or `MultiBar::new` detect the
+/// capabilities of stdout. Bars created with `on` can't tell whether their
+/// handle is a terminal, so they assume it is not: the cursor is left
+/// alone and colors are only used if forced. Use `set_capabilities` on
+/// such a bar to draw it as on a terminal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+    /// Color escape codes.
+    pub color: bool,
+    /// Cursor movement, to redraw a `MultiBar` and hide the cursor.
+    pub cursor: bool,
+    /// Unicode glyphs, otherwise the ASCII fallbacks are used.
+    pub unicode: bool,
+    /// Redraw the bar in place with "\r", otherwise print a line now and
+    /// then, as in a log.
+    pub redraw: bool,
+    /// The output is a terminal, so the cursor is hidden while drawing.
+    pub terminal: bool,
+}
+
+impl Default for Capabilities {
+    /// Redraw in place with Unicode glyphs, but without colors, as on an
+    /// output that is not a terminal.
+    fn default() -> Capabilities {
+        Capabilities {
+            color: false,
+            cursor: true,
+            unicode: true,
+            redraw: true,
+            terminal: false,
+        }
+    }
+}
+
+impl Capabilities {
+    /// Detect the capabilities of stdout:
+    ///
+    /// - colors are used on a terminal, unless `NO_COLOR` is set, or
+    ///   anywhere if `CLICOLOR_FORCE` is set to anything but "0";
+    /// - `TERM=dumb` turns off colors, cursor movement and redrawing;
+    /// - `CI` turns off redrawing, so logs get one line now and then;
+    /// - Unicode is used unless `LC_ALL`, `LC_CTYPE` or `LANG` names a
+    ///   locale that is not UTF-8, like "C" or "POSIX".
+    pub fn detect() -> Capabilities {
+        Capabilities::from_vars(terminal_size().is_some(), |name| env::var(name).ok())
+    }
+
+    // detect_handle detects the capabilities of a handle that is not known
+    // to be a terminal, see `ProgressBar::on`.
+    pub(crate) fn detect_handle() -> Capabilities {
+        Capabilities::from_vars(false, |name| env::var(name).ok())
+    }
+
+    // from_vars detects the capabilities from whether the output is a
+    // terminal and the environment variables looked up with `var`.
+    // `is_some_and` and `is_none_or` would need Rust 1.70 and 1.82.
+    #[allow(clippy::unnecessary_map_or)]
+    pub(crate) fn from_vars<F>(tty: bool, var: F) -> Capabilities
+        where F: Fn(&str) -> Option<String>
+    {
+        let set = |name| var(name).map_or(false, |v| !v.is_empty());
+        let dumb = var("TERM").map_or(false, |t| t == "dumb");
+        let forced = var("CLICOLOR_FORCE").map_or(false, |v| !v.is_empty() && v != "0");
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|name| var(name))
+            .find(|v| !v.is_empty());
+        let unicode = locale.map_or(true, |l| {
+            let l = l.to_lowercase();
+            l.contains("utf-8") || l.contains("utf8")
+        });
+        Capabilities {
+            color: !set("NO_COLOR") && (forced || tty && !dumb),
+            cursor: !dumb,
+            unicode,
+            redraw: !dumb && !set("CI"),
+            terminal: tty,
+        }
+    }
+}
@@ -106,18 +106,35 @@
 // ```
 macro_rules! printfl {
    ($w:expr, $($tt:tt)*) => {{
-        $w.write(&format!($($tt)*).as_bytes()).ok().expect("write() fail");
+        $w.write_all(format!($($tt)*).as_bytes()).ok().expect("write() fail");
         $w.flush().ok().expect("flush() fail");
     }}
 }
 
-#[macro_use]
 extern crate time;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "toml")]
+extern crate toml;
 mod tty;
+mod env;
 mod pb;
 mod multi;
-pub use pb::{ProgressBar, Units};
+mod widget;
+mod number;
+mod style;
+mod builder;
+pub use pb::{ProgressBar, Units, Color, FinishState, DropBehavior, OverflowBehavior, Stats,
+             ProgressState, Truncation, NonTtyBehavior};
 pub use multi::{MultiBar, Pipe};
+pub use env::Capabilities;
+pub use widget::{Widget, Builtin};
+pub use number::NumberFormat;
+pub use style::ProgressStyle;
+#[cfg(feature = "config")]
+pub use style::ConfigError;
+pub use builder::{ProgressBarBuilder, BuildError};
 use std::io::{Write, Stdout, stdout};
 
 pub struct PbIter<T, I>
@@ -143,10 +160,16 @@ impl<T, I> PbIter<T, I>
     pub fn on(handle: T, iter: I) -> Self {
         let size = iter.size_hint().0;
         PbIter {
-            iter: iter,
+            iter,
             progress_bar: ProgressBar::on(handle, size as u64),
         }
     }
+
+    /// Set what the bar does when the iterator is dropped before it was
+    /// exhausted, see `ProgressBar::set_drop_behavior`.
+    pub fn set_drop_behavior(&mut self, behavior: DropBehavior) {
+        self.progress_bar.set_drop_behavior(behavior);
+    }
 }
 
 impl<T, I> Iterator for PbIter<T, I>
@@ -161,7 +184,12 @@ impl<T, I> Iterator for PbIter<T, I>
                 self.progress_bar.inc();
                 Some(i)
             }
-            None => None,
+            None => {
+                if !self.progress_bar.is_finish {
+                    self.progress_bar.finish();
+                }
+                None
+            }
         }
     }
 
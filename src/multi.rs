@@ -1,19 +1,38 @@
-use pb::ProgressBar;
+use pb::{ProgressBar, FinishState, fract_dur};
+use style::ProgressStyle;
+use env::Capabilities;
 use std::str::from_utf8;
-use tty::move_cursor_up;
+use tty::{Width, move_cursor_up, terminal_size, hide_cursor, show_cursor};
 use std::io::{Stdout, Result, Write};
 use std::sync::mpsc;
-use std::sync::mpsc::{Sender, Receiver};
+use std::time::Duration;
+use std::sync::mpsc::{Sender, Receiver, SendError};
 
 pub struct MultiBar<T: Write> {
     nlines: usize,
 
-    lines: Vec<String>,
+    // lines of removed bars are `None`
+    lines: Vec<Option<String>>,
+
+    // number of lines printed by the last draw
+    drawn: usize,
 
     nbars: usize,
 
+    states: Vec<Option<FinishState>>,
+
+    durations: Vec<Option<Duration>>,
+
+    style: Option<ProgressStyle>,
+
+    caps: Capabilities,
+
     chan: (Sender<WriteMsg>, Receiver<WriteMsg>),
 
+    hide_cursor: bool,
+
+    cursor_hidden: bool,
+
     handle: T,
 }
 
@@ -24,8 +43,10 @@ impl MultiBar<Stdout> {
     ///
     /// ```no_run
     /// use std::thread;
+    /// use std::time::Duration;
     /// use pbr::MultiBar;
     ///
+    /// let count = 100;
     /// let mut mb = MultiBar::new();
     /// mb.println("Application header:");
     ///
@@ -57,13 +78,23 @@ impl MultiBar<Stdout> {
     /// mb.listen();
     /// ```
     pub fn new() -> MultiBar<Stdout> {
-        MultiBar::on(::std::io::stdout())
+        let mut mb = MultiBar::on(::std::io::stdout());
+        mb.set_capabilities(Capabilities::detect());
+        mb
+    }
+}
+
+impl Default for MultiBar<Stdout> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl<T: Write> MultiBar<T> {
     /// Create a new MultiBar with an arbitrary writer.
     ///
+    /// The writer is not assumed to be a terminal, see `Capabilities`.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -80,8 +111,15 @@ impl<T: Write> MultiBar<T> {
             nlines: 0,
             nbars: 0,
             lines: Vec::new(),
+            drawn: 0,
+            states: Vec::new(),
+            durations: Vec::new(),
+            style: None,
+            caps: Capabilities::detect_handle(),
             chan: mpsc::channel(),
-            handle: handle,
+            hide_cursor: true,
+            cursor_hidden: false,
+            handle,
         }
     }
 
@@ -94,15 +132,16 @@ impl<T: Write> MultiBar<T> {
     /// ```no_run
     /// use pbr::MultiBar;
     ///
+    /// let count = 100;
     /// let mut mb = MultiBar::new();
     /// mb.println("Application header:");
     ///
-    /// let mut p1 = MultiBar::create_bar(count);
+    /// let mut p1 = mb.create_bar(count);
     /// // ...
     ///
     /// mb.println("Text line between bar1 and bar2");
     ///
-    /// let mut p2 = MultiBar::create_bar(count);
+    /// let mut p2 = mb.create_bar(count);
     /// // ...
     ///
     /// mb.println("Text line between bar2 and bar3");
@@ -112,17 +151,62 @@ impl<T: Write> MultiBar<T> {
     /// mb.listen();
     /// ```
     pub fn println(&mut self, s: &str) {
-        self.lines.push(s.to_owned());
+        self.lines.push(Some(s.to_owned()));
         self.nlines += 1;
     }
 
+    /// Set whether the cursor should be hidden while `listen` is drawing
+    /// the bars, default is `true`.
+    ///
+    /// The cursor is only hidden if the output is a terminal, see
+    /// `Capabilities::terminal`, and it is shown again when all bars
+    /// finished or the `MultiBar` is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    ///
+    /// let mut mb = MultiBar::new();
+    /// mb.set_hide_cursor(false);
+    /// ```
+    pub fn set_hide_cursor(&mut self, hide: bool) {
+        self.hide_cursor = hide;
+    }
+
+    /// Set the style of the bars created afterwards, see `ProgressStyle`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{MultiBar, ProgressStyle};
+    ///
+    /// let mut mb = MultiBar::new();
+    /// mb.set_style(&ProgressStyle::docker());
+    /// let mut p1 = mb.create_bar(100);
+    /// p1.message("a3ed95caeb02: Downloading ");
+    /// ```
+    pub fn set_style(&mut self, style: &ProgressStyle) {
+        self.style = Some(style.clone());
+    }
+
+    /// Set what the output can render, for the `MultiBar` and the bars
+    /// created afterwards, see `ProgressBar::set_capabilities`.
+    ///
+    /// Without cursor movement or redrawing, the bars are printed as log
+    /// lines.
+    pub fn set_capabilities(&mut self, caps: Capabilities) {
+        self.caps = caps;
+    }
+
     /// create_bar creates new `ProgressBar` with `Pipe` as the writer.
     ///
     /// The ordering of the method calls is important. it means that in
     /// the first call, you get a progress bar in level 1, in the 2nd call,
     /// you get a progress bar in level 2, and so on.
     ///
-    /// ProgressBar that finish its work, must call `finish()` (or `finish_print`)
+    /// ProgressBar that finish its work, must call `finish()` (or `finish_print`,
+    /// `finish_with_error`, `abandon`)
     /// to notify the `MultiBar` about it.
     ///
     /// # Examples
@@ -130,18 +214,19 @@ impl<T: Write> MultiBar<T> {
     /// ```no_run
     /// use pbr::MultiBar;
     ///
+    /// let (count1, count2, count3) = (100, 200, 300);
     /// let mut mb = MultiBar::new();
     ///
     /// // progress bar in level 1
-    /// let mut p1 = MultiBar::create_bar(count1);
+    /// let mut p1 = mb.create_bar(count1);
     /// // ...
     ///
     /// // progress bar in level 2
-    /// let mut p2 = MultiBar::create_bar(count2);
+    /// let mut p2 = mb.create_bar(count2);
     /// // ...
     ///
     /// // progress bar in level 3
-    /// let mut p3 = MultiBar::create_bar(count3);
+    /// let mut p3 = mb.create_bar(count3);
     ///
     /// // ...
     /// mb.listen();
@@ -149,20 +234,107 @@ impl<T: Write> MultiBar<T> {
     pub fn create_bar(&mut self, total: u64) -> ProgressBar<Pipe> {
         self.println("");
         self.nbars += 1;
-        let mut p = ProgressBar::on(Pipe {
-                                        level: self.nlines - 1,
-                                        chan: self.chan.0.clone(),
-                                    },
-                                    total);
+        self.states.push(None);
+        self.durations.push(None);
+        let (level, bar) = (self.nlines - 1, self.nbars - 1);
+        let pipe = || {
+            Pipe {
+                level,
+                bar,
+                chan: self.chan.0.clone(),
+            }
+        };
+        let mut p = ProgressBar::on(pipe(), total);
         p.is_multibar = true;
+        p.multibar = Some(pipe());
+        if let Some(ref style) = self.style {
+            p.set_style(style);
+        }
+        p.set_capabilities(self.caps);
         p.add(0);
         p
     }
 
+    /// create_child_bar creates a new `ProgressBar` like `create_bar`, and
+    /// attaches it as a weighted child to `parent` (see
+    /// `ProgressBar::add_child`).
+    ///
+    /// The child bar is drawn indented below the previous line.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    ///
+    /// let mut mb = MultiBar::new();
+    /// let mut pb = mb.create_bar(100);
+    /// let mut download = mb.create_child_bar(&mut pb, 80., 1024);
+    /// let mut verify = mb.create_child_bar(&mut pb, 20., 1024);
+    /// // ...
+    /// mb.listen();
+    /// ```
+    pub fn create_child_bar<W: Write>(&mut self,
+                                      parent: &mut ProgressBar<W>,
+                                      weight: f64,
+                                      total: u64)
+                                      -> ProgressBar<Pipe> {
+        let mut p = self.create_bar(total);
+        p.indent = parent.indent + 2;
+        parent.add_child(&mut p, weight);
+        p
+    }
+
+    /// Get the states the bars finished in, in the order they were created.
+    ///
+    /// A bar that didn't finish (yet) has the state `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{MultiBar, FinishState};
+    ///
+    /// let mut mb = MultiBar::new();
+    /// // ...
+    /// mb.listen();
+    ///
+    /// let failed = mb.finish_states()
+    ///     .iter()
+    ///     .filter(|s| matches!(s, Some(FinishState::Error(_))))
+    ///     .count();
+    /// println!("{} bars failed", failed);
+    /// ```
+    pub fn finish_states(&self) -> &[Option<FinishState>] {
+        &self.states
+    }
+
+
+    /// Get a table of the final state and duration of every bar, in the
+    /// order they were created.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::MultiBar;
+    ///
+    /// let mut mb = MultiBar::new();
+    /// // ...
+    /// mb.listen();
+    /// println!("{}", mb.summary());
+    /// ```
+    pub fn summary(&self) -> String {
+        let mut out = format!("{:<4} {:>10}  {}", "bar", "time", "state");
+        for (i, (state, elapsed)) in self.states.iter().zip(self.durations.iter()).enumerate() {
+            let elapsed = elapsed.map_or("-".to_owned(), |d| format!("{:.2}s", fract_dur(d)));
+            let state = state.as_ref().map_or("running".to_owned(), |s| s.to_string());
+            out += &format!("\n{:<4} {:>10}  {}", i + 1, elapsed, state);
+        }
+        out
+    }
 
     /// listen start listen to all bars changes.
     ///
-    /// `ProgressBar` that finish its work, must call `finish()` (or `finish_print`)
+    /// `ProgressBar` that finish its work, must call `finish()` (or `finish_print`,
+    /// `finish_with_error`, `abandon`)
     /// to notify the `MultiBar` about it.
     ///
     /// This is a blocking operation and blocks until all bars will
@@ -172,6 +344,7 @@ impl<T: Write> MultiBar<T> {
     /// # Examples
     ///
     /// ```no_run
+    /// use std::thread;
     /// use pbr::MultiBar;
     ///
     /// let mut mb = MultiBar::new();
@@ -190,48 +363,127 @@ impl<T: Write> MultiBar<T> {
     pub fn listen(&mut self) {
         let mut first = true;
         let mut nbars = self.nbars;
+        let log = !self.caps.redraw || !self.caps.cursor;
+        if log {
+            for l in self.lines.iter().flatten().filter(|l| !l.is_empty()) {
+                printfl!(self.handle, "{}\n", l);
+            }
+        }
         while nbars > 0 {
 
             // receive message
             let msg = self.chan.1.recv().unwrap();
-            if msg.done {
-                nbars -= 1;
-                continue;
+            match msg.kind {
+                // print each line the bars send, as they come
+                MsgKind::Draw(s) if log => {
+                    let line = s.trim_matches(|c| c == '\r' || c == '\n').trim_end();
+                    if !line.is_empty() {
+                        printfl!(self.handle, "{}\n", line);
+                    }
+                    continue;
+                }
+                MsgKind::Remove if log => continue,
+                MsgKind::Draw(s) => {
+                    if let Some(ref mut line) = self.lines[msg.level] {
+                        *line = s;
+                    }
+                }
+                MsgKind::Remove => self.lines[msg.level] = None,
+                MsgKind::Done(state, elapsed) => {
+                    self.states[msg.bar] = Some(state);
+                    self.durations[msg.bar] = Some(elapsed);
+                    nbars -= 1;
+                    continue;
+                }
             }
-            self.lines[msg.level] = msg.string;
 
             // and draw
             let mut out = String::new();
             if !first {
-                out += &move_cursor_up(self.nlines);
+                out += &move_cursor_up(self.drawn);
             } else {
                 first = false;
+                if self.hide_cursor && self.caps.cursor && self.caps.terminal {
+                    out += &hide_cursor();
+                    self.cursor_hidden = true;
+                }
             }
-            for l in self.lines.iter() {
+            let mut count = 0;
+            for l in self.lines.iter().flatten() {
                 out.push_str(&format!("\r{}\n", l));
+                count += 1;
+            }
+            // blank out the lines left over from removed bars
+            let gone = self.drawn.saturating_sub(count);
+            if gone > 0 {
+                let width = terminal_size().map_or(80, |(Width(w), _)| w as usize);
+                let blank = " ".repeat(width);
+                for _ in 0..gone {
+                    out.push_str(&format!("\r{}\n", blank));
+                }
             }
             printfl!(self.handle, "{}", out);
+            // and move back up, so the next draw starts below the last bar
+            if gone > 0 {
+                printfl!(self.handle, "{}", move_cursor_up(gone));
+            }
+            self.drawn = count;
+        }
+        self.restore_cursor();
+    }
+
+    // restore_cursor shows the cursor again if it was hidden by `listen`.
+    fn restore_cursor(&mut self) {
+        if self.cursor_hidden {
+            self.cursor_hidden = false;
+            // this also runs on drop, so don't panic on write errors.
+            let _ = self.handle.write_all(show_cursor().as_bytes());
+            let _ = self.handle.flush();
         }
     }
 }
 
+impl<T: Write> Drop for MultiBar<T> {
+    fn drop(&mut self) {
+        self.restore_cursor();
+    }
+}
+
 pub struct Pipe {
     level: usize,
+    bar: usize,
     chan: Sender<WriteMsg>,
 }
 
+impl Pipe {
+    // finish tells the MultiBar that the bar finished in the given state,
+    // after running for `elapsed`.
+    pub(crate) fn finish(&self, state: FinishState, elapsed: Duration) {
+        // may be called on drop, when the MultiBar is already gone.
+        let _ = self.send(MsgKind::Done(state, elapsed));
+    }
+
+    // remove removes the line of the bar, the lines below it move up.
+    pub(crate) fn remove(&self) {
+        let _ = self.send(MsgKind::Remove);
+    }
+
+    fn send(&self, kind: MsgKind) -> ::std::result::Result<(), SendError<WriteMsg>> {
+        self.chan.send(WriteMsg {
+            level: self.level,
+            bar: self.bar,
+            kind,
+        })
+    }
+}
+
 impl Write for Pipe {
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
         let s = from_utf8(buf).unwrap().to_owned();
-        self.chan
-            .send(WriteMsg {
-                // finish method emit empty string
-                done: s == "",
-                level: self.level,
-                string: s,
-            })
-            .unwrap();
-        Ok(1)
+        // nothing listens once the MultiBar is gone, and bars still draw
+        // when they are dropped, so drop the line instead of failing.
+        let _ = self.send(MsgKind::Draw(s));
+        Ok(buf.len())
     }
 
     fn flush(&mut self) -> Result<()> {
@@ -242,7 +494,16 @@ impl Write for Pipe {
 // WriteMsg is the message format used to communicate
 // between MultiBar and its bars
 struct WriteMsg {
-    done: bool,
     level: usize,
-    string: String,
+    bar: usize,
+    kind: MsgKind,
+}
+
+enum MsgKind {
+    // the bar was redrawn
+    Draw(String),
+    // the line of the bar should be removed
+    Remove,
+    // the bar finished in the given state, after running for the duration
+    Done(FinishState, Duration),
 }
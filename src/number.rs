@@ -0,0 +1,102 @@
+// SI suffixes of compact numbers, for each power of 1000
+const SUFFIXES: [&str; 5] = ["k", "M", "G", "T", "P"];
+
+/// How numbers are written in the counter and speed boxes.
+///
+/// The default writes plain numbers, "1234567.50".
+///
+/// # Examples
+///
+/// ```
+/// use pbr::NumberFormat;
+///
+/// let nf = NumberFormat::locale("de_DE.UTF-8");
+/// assert_eq!(nf.format(1234567.5, 2), "1.234.567,50");
+///
+/// let nf = NumberFormat { compact: true, ..NumberFormat::default() };
+/// assert_eq!(nf.format(1234567., 0), "1.2M");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberFormat {
+    /// Separator between groups of thousands, none by default.
+    pub thousands: Option<char>,
+    /// Separator before the decimals, '.' by default.
+    pub decimal: char,
+    /// Write numbers from 1000 on with SI suffixes, "1.2M".
+    pub compact: bool,
+}
+
+impl Default for NumberFormat {
+    fn default() -> NumberFormat {
+        NumberFormat {
+            thousands: None,
+            decimal: '.',
+            compact: false,
+        }
+    }
+}
+
+impl NumberFormat {
+    /// Create a format with thousands grouping, from a locale hint like
+    /// "en_US", "de_DE.UTF-8" or "fr". Only the language is used; unknown
+    /// languages get the English separators.
+    pub fn locale(locale: &str) -> NumberFormat {
+        let lang = locale.split(['_', '-', '.']).next().unwrap_or("");
+        let (thousands, decimal) = match lang {
+            "de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" => ('.', ','),
+            "fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "no" | "uk" | "hu" => (' ', ','),
+            _ => (',', '.'),
+        };
+        NumberFormat {
+            thousands: Some(thousands),
+            decimal,
+            compact: false,
+        }
+    }
+
+    /// Format a number with the given number of decimals. Compact numbers
+    /// always get one decimal.
+    pub fn format(&self, n: f64, precision: usize) -> String {
+        // round first, 999.96 with no decimals is "1.0k" and not "1000"
+        let scale = 10f64.powi(precision as i32);
+        if self.compact && ((n * scale).round() / scale).abs() >= 1000. {
+            let mut n = n / 1000.;
+            let mut i = 0;
+            // check the rounded number, 999_950 is "1.0M" and not "1000.0k"
+            let round = |n: f64| (n * 10.).round() / 10.;
+            while round(n).abs() >= 1000. && i + 1 < SUFFIXES.len() {
+                n /= 1000.;
+                i += 1;
+            }
+            return self.separate(&format!("{:.1}", n)) + SUFFIXES[i];
+        }
+        self.separate(&format!("{:.*}", precision, n))
+    }
+
+    // separate puts the separators in a number formatted by Display.
+    fn separate(&self, s: &str) -> String {
+        let (sign, s) = if let Some(s) = s.strip_prefix('-') {
+            ("-", s)
+        } else {
+            ("", s)
+        };
+        let (int, frac) = match s.find('.') {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+        let mut out = sign.to_owned();
+        for (i, c) in int.chars().enumerate() {
+            if i > 0 && (int.len() - i) % 3 == 0 {
+                if let Some(sep) = self.thousands {
+                    out.push(sep);
+                }
+            }
+            out.push(c);
+        }
+        if let Some(frac) = frac {
+            out.push(self.decimal);
+            out += frac;
+        }
+        out
+    }
+}
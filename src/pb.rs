@@ -1,9 +1,17 @@
+use std::cmp::Reverse;
+use std::fmt;
 use std::io::{self, Write};
 use std::iter::repeat;
 use std::time::Duration;
 use time::{self, SteadyTime};
-use std::io::Stdout;
-use tty::{Width, terminal_size};
+use std::io::{Stdout, Sink};
+use std::sync::{Arc, Mutex, Weak};
+use tty::{Width, terminal_size, hide_cursor, show_cursor};
+use multi::Pipe;
+use number::NumberFormat;
+use style::ProgressStyle;
+use env::Capabilities;
+use widget::{Widget, Builtin, BuiltinBox, Slot, DEFAULT_LAYOUT, parse_layout};
 
 macro_rules! kb_fmt {
     ($n: ident) => {{
@@ -24,34 +32,434 @@ macro_rules! repeat {
     }}
 }
 
-const FORMAT: &'static str = "[=>-]";
-const TICK_FORMAT: &'static str = "\\|/-";
+pub(crate) const FORMAT: &str = "[=>-]";
+pub(crate) const TICK_FORMAT: &str = "\\|/-";
+pub(crate) const BUFFERED_GLYPH: &str = "~";
+const ERROR_FORMAT: &str = "[=X-]";
 const NANOS_PER_SEC: u32 = 1_000_000_000;
+// widest speed box when the boxes have a fixed width, as "1023.99 KB/s"
+const SPEED_WIDTH: usize = 12;
+// least time between two lines of a bar printed as a log
+const LOG_INTERVAL_SECS: i64 = 5;
+// pauses in progress longer than this count as stalled
+const STALL_SECS: i64 = 1;
+// window the peak speed is measured over
+const PEAK_WINDOW_MILLIS: i64 = 500;
 
 // Output type format, indicate which format wil be used in
 // the speed box.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "lowercase"))]
 pub enum Units {
     Default,
     Bytes,
 }
 
+/// Terminal colors used to draw the bar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "lowercase"))]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl Color {
+    // paint wraps `s` in the ANSI escape codes of this color.
+    fn paint(&self, s: &str) -> String {
+        format!("\x1B[{}m{}\x1B[0m", 30 + *self as u8, s)
+    }
+}
+
+/// The state a progress bar finished in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FinishState {
+    /// The bar finished with `finish()` (or one of its variants).
+    Success,
+    /// The bar finished with `finish_with_error(msg)`.
+    Error(String),
+    /// The bar was abandoned with `abandon(msg)`.
+    Abandoned(String),
+}
+
+impl fmt::Display for FinishState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FinishState::Success => write!(f, "done"),
+            FinishState::Error(ref msg) => write!(f, "error: {}", msg),
+            FinishState::Abandoned(ref msg) => write!(f, "abandoned: {}", msg),
+        }
+    }
+}
+
+/// What a progress bar does when it is dropped before it finished.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DropBehavior {
+    /// Call `finish()`, the bar is drawn as completed.
+    Finish,
+    /// Call `abandon()` with an empty message, the bar is kept at its
+    /// current position. This is the default.
+    Abandon,
+    /// Erase the bar completely.
+    Clear,
+    /// Leave the bar as it was drawn the last time.
+    Leave,
+}
+
+/// What a progress bar does when its output is not a terminal, e.g. when
+/// it is piped to a file, see `Capabilities::terminal`.
+///
+/// To print the bar as a line now and then, as in a log, turn off
+/// `Capabilities::redraw` instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "lowercase"))]
+pub enum NonTtyBehavior {
+    /// Draw the bar as on a terminal. This is the default.
+    Draw,
+    /// Do not draw the bar, only the lines printed with `finish_print`
+    /// and `finish_println`.
+    Hide,
+}
+
+/// What a progress bar does when its current value goes above its total.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverflowBehavior {
+    /// Keep current at total.
+    Clamp,
+    /// Grow total along with current.
+    Grow,
+    /// Draw a full bar, and show "(+N over)" in the counter box. This is
+    /// the default.
+    Show,
+}
+
+/// How a message that is too long for its box is shortened.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Truncation {
+    /// Cut the end, "copying fi…". This is the default.
+    End,
+    /// Cut the start, "…ying files".
+    Start,
+    /// Cut the middle, "copy…files".
+    Middle,
+    /// Shorten the leading directories of a path to their first letter,
+    /// "/h/u/p/very_long_name.rs", then cut the start if needed.
+    Path,
+}
+
+// A named secondary counter, drawn in the counter box after the main one.
+struct Counter {
+    name: String,
+    units: Units,
+    current: u64,
+    total: u64,
+    show_speed: bool,
+    show_time_left: bool,
+}
+
+// A category of finished items, drawn as its own segment of the bar.
+struct Segment {
+    name: String,
+    glyph: String,
+    color: Option<Color>,
+    count: u64,
+}
+
+// The progress of a child bar, shared with its parent.
+struct ChildShare {
+    weight: f64,
+    current: u64,
+    total: u64,
+    // the parent to redraw, if it was shared with `into_shared`
+    redraw: Option<Weak<Mutex<dyn Redraw + Send>>>,
+}
+
+// Redraw lets a child bar redraw its parent, whatever the parent's writer.
+trait Redraw {
+    fn redraw_for_child(&mut self);
+}
+
+impl<T: Write> Redraw for ProgressBar<T> {
+    fn redraw_for_child(&mut self) {
+        if !self.is_finish {
+            self.draw();
+        }
+    }
+}
+
+impl ChildShare {
+    fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            0.
+        } else {
+            (self.current as f64 / self.total as f64).min(1.)
+        }
+    }
+}
+
+/// Summary statistics of a progress bar run, see `ProgressBar::stats`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    /// Time since the bar started, without the time it was paused.
+    pub elapsed: Duration,
+    /// Average speed, per second.
+    pub average_speed: f64,
+    /// Highest speed measured over half a second.
+    pub peak_speed: f64,
+    /// Time without any progress, counting only pauses longer than a second.
+    pub stalled: Duration,
+}
+
+/// A read-only snapshot of a progress bar, see `ProgressBar::state`.
+///
+/// Positions and rates are in the bar's units, including the fractional
+/// parts set with `set_f64` and friends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressState {
+    /// The current position.
+    pub position: f64,
+    /// The total, the position at which the bar is full.
+    pub total: f64,
+    /// How full the bar is, usually between `0.0` and `1.0`.
+    pub fraction: f64,
+    /// Time since the bar started, without the time it was paused.
+    pub elapsed: Duration,
+    /// Current speed, per second.
+    pub rate: f64,
+    /// Estimated time left, if there is a speed to base it on.
+    pub eta: Option<Duration>,
+    /// The message shown before the counter.
+    pub message: String,
+    /// Whether the bar is paused.
+    pub paused: bool,
+    /// How the bar finished, `None` while it is still running.
+    pub finish_state: Option<FinishState>,
+}
+
+// Boxes is what the built-in boxes are rendered from, prepared for each
+// draw. All of them but the bar are rendered up front, the message is
+// shortened to the width left.
+pub(crate) struct Boxes {
+    text: Vec<(Builtin, String)>,
+    truncation: Truncation,
+    ellipsis: &'static str,
+    bar: Option<BarBox>,
+}
+
+impl Boxes {
+    // render renders a built-in box with the width left for it.
+    pub(crate) fn render(&self, b: Builtin, width: usize) -> String {
+        if b == Builtin::Bar {
+            // the last column is left free
+            return self.bar
+                .as_ref()
+                .map_or_else(String::new, |bar| bar.render(width.saturating_sub(3)));
+        }
+        let text = self.text.iter().find(|t| t.0 == b).map_or("", |t| &t.1[..]);
+        if b == Builtin::Message && display_len(text) > width {
+            truncate_message(text, width, self.truncation, self.ellipsis)
+        } else {
+            text.to_owned()
+        }
+    }
+}
+
+// BarBox is what the bar box is drawn from, with the glyphs and colors the
+// output can render.
+struct BarBox {
+    glyphs: Vec<String>,
+    fill_current: f64,
+    fill_total: f64,
+    // the glyph, count and color of each segment
+    segments: Vec<(String, u64, Option<Color>)>,
+    buffered: f64,
+    buffered_glyph: String,
+    buffered_color: Option<Color>,
+    color: Option<Color>,
+}
+
+impl BarBox {
+    // render renders the bar box with `size` cells between its ends.
+    fn render(&self, size: usize) -> String {
+        let fill_total = self.fill_total;
+        let glyphs = &self.glyphs;
+        // cells counts the cells for `v` units, an empty total has none
+        let cells = |v: f64, max: usize| if fill_total <= 0. {
+            0
+        } else {
+            ((v / fill_total) * size as f64)
+                .ceil()
+                .min(max as f64) as usize
+        };
+        let curr_count = cells(self.fill_current, size);
+        let rema_count = size - curr_count;
+        let mut base = glyphs[0].to_owned();
+        if !self.segments.is_empty() {
+            // stack the segments, the rest of current follows them
+            let (mut done, mut sum) = (0, 0);
+            for &(ref glyph, count, color) in &self.segments {
+                sum += count;
+                let n = cells(sum as f64, curr_count) - done;
+                let piece = repeat!(&glyph[..], n);
+                base += &match color {
+                    Some(color) => color.paint(piece),
+                    None => piece.to_owned(),
+                };
+                done += n;
+            }
+            base += repeat!(&glyphs[1][..], curr_count - done);
+        } else if rema_count > 0 && curr_count > 0 {
+            base += repeat!(&glyphs[1][..], curr_count - 1);
+            base += &glyphs[2];
+        } else {
+            base += repeat!(&glyphs[1][..], curr_count);
+        }
+        // the buffered part is taken from the remaining one
+        let buffered_count = cells(self.buffered, size).saturating_sub(curr_count);
+        if buffered_count > 0 {
+            let piece = repeat!(&self.buffered_glyph[..], buffered_count);
+            base += &match self.buffered_color {
+                Some(color) => color.paint(piece),
+                None => piece.to_owned(),
+            };
+            base += repeat!(&glyphs[3][..], rema_count - buffered_count);
+        } else {
+            base += repeat!(&glyphs[3][..], rema_count);
+        }
+        base += &glyphs[4];
+        // colorize a failed bar
+        match self.color {
+            Some(color) => color.paint(&base),
+            None => base,
+        }
+    }
+}
+
+// StatsTracker keeps the state needed to compute `Stats`, all times are
+// elapsed times.
+struct StatsTracker {
+    end: Option<time::Duration>,
+    last_change: time::Duration,
+    last_current: f64,
+    sample_time: time::Duration,
+    sample_current: f64,
+    peak_speed: f64,
+    stalled: time::Duration,
+}
+
+impl StatsTracker {
+    fn new() -> StatsTracker {
+        StatsTracker {
+            end: None,
+            last_change: time::Duration::zero(),
+            last_current: 0.,
+            sample_time: time::Duration::zero(),
+            sample_current: 0.,
+            peak_speed: 0.,
+            stalled: time::Duration::zero(),
+        }
+    }
+
+    fn update(&mut self, elapsed: time::Duration, current: f64) {
+        if self.end.is_some() {
+            return;
+        }
+        if current != self.last_current {
+            self.stall_until(elapsed);
+            self.last_change = elapsed;
+            self.last_current = current;
+        }
+        let dt = elapsed - self.sample_time;
+        if dt >= time::Duration::milliseconds(PEAK_WINDOW_MILLIS) {
+            let speed = (current - self.sample_current).max(0.) / fract_dur(time_to_std(dt));
+            self.peak_speed = self.peak_speed.max(speed);
+            self.sample_time = elapsed;
+            self.sample_current = current;
+        }
+    }
+
+    fn stall_until(&mut self, elapsed: time::Duration) {
+        let gap = elapsed - self.last_change;
+        if gap > time::Duration::seconds(STALL_SECS) {
+            self.stalled = self.stalled + gap;
+        }
+    }
+
+    fn finish(&mut self, elapsed: time::Duration, current: f64) {
+        if self.end.is_none() {
+            self.update(elapsed, current);
+            self.stall_until(elapsed);
+            self.end = Some(elapsed);
+        }
+    }
+}
+
 pub struct ProgressBar<T: Write> {
     start_time: SteadyTime,
     units: Units,
     pub total: u64,
     current: u64,
+    // the fractional parts of current and total, see `set_f64`
+    current_frac: f64,
+    total_frac: f64,
+    precision: usize,
+    counters: Vec<Counter>,
+    fill_counter: Option<String>,
+    segments: Vec<Segment>,
+    children: Vec<Arc<Mutex<ChildShare>>>,
+    parent: Option<Arc<Mutex<ChildShare>>>,
+    shared: Option<Weak<Mutex<dyn Redraw + Send>>>,
+    pub(crate) indent: usize,
+    stages: Vec<String>,
+    stage: Option<usize>,
+    stage_times: Vec<Duration>,
+    stats: StatsTracker,
+    layout: Vec<Slot>,
+    bar_min_width: usize,
+    widgets: Vec<(String, Box<dyn Widget>)>,
+    ascii_format: String,
     bar_start: String,
     bar_current: String,
     bar_current_n: String,
     bar_remain: String,
     bar_end: String,
+    bar_buffered: String,
+    buffered: u64,
+    buffered_color: Option<Color>,
+    error_format: Vec<String>,
+    error_color: Option<Color>,
     tick: Vec<String>,
+    ascii_tick: Vec<String>,
     tick_state: usize,
     width: Option<usize>,
     message: String,
+    message_width: Option<usize>,
+    truncation: Truncation,
+    fixed_box_width: bool,
+    number_format: NumberFormat,
+    percent_precision: usize,
     last_refresh_time: SteadyTime,
     max_refresh_rate: Option<time::Duration>,
+    paused_at: Option<SteadyTime>,
+    paused_time: time::Duration,
+    rate_elapsed: time::Duration,
+    rate_current: f64,
+    hide_cursor: bool,
+    cursor_hidden: bool,
+    finish_state: Option<FinishState>,
+    drop_behavior: DropBehavior,
+    non_tty: NonTtyBehavior,
+    caps: Capabilities,
+    last_log: Option<SteadyTime>,
+    log_pending: bool,
+    leave_on_finish: bool,
+    overflow: OverflowBehavior,
+    pub(crate) multibar: Option<Pipe>,
     pub is_finish: bool,
     pub is_multibar: bool,
     pub show_bar: bool,
@@ -84,7 +492,9 @@ impl ProgressBar<Stdout> {
     /// ```
     pub fn new(total: u64) -> ProgressBar<Stdout> {
         let handle = ::std::io::stdout();
-        ProgressBar::on(handle, total)
+        let mut pb = ProgressBar::on(handle, total);
+        pb.set_capabilities(Capabilities::detect());
+        pb
     }
 }
 
@@ -92,6 +502,8 @@ impl<T: Write> ProgressBar<T> {
     /// Create a new ProgressBar with default configuration but
     /// pass an arbitrary writer.
     ///
+    /// The writer is not assumed to be a terminal, see `Capabilities`.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -110,8 +522,25 @@ impl<T: Write> ProgressBar<T> {
     /// ```
     pub fn on(handle: T, total: u64) -> ProgressBar<T> {
         let mut pb = ProgressBar {
-            total: total,
+            total,
             current: 0,
+            current_frac: 0.,
+            total_frac: 0.,
+            precision: 0,
+            counters: Vec::new(),
+            fill_counter: None,
+            segments: Vec::new(),
+            children: Vec::new(),
+            parent: None,
+            shared: None,
+            indent: 0,
+            stages: Vec::new(),
+            stage: None,
+            stage_times: Vec::new(),
+            stats: StatsTracker::new(),
+            layout: parse_layout(DEFAULT_LAYOUT),
+            bar_min_width: 10,
+            widgets: Vec::new(),
             start_time: SteadyTime::now(),
             units: Units::Default,
             is_finish: false,
@@ -123,21 +552,50 @@ impl<T: Write> ProgressBar<T> {
             show_time_left: true,
             show_tick: false,
             show_message: true,
+            ascii_format: FORMAT.to_owned(),
             bar_start: String::new(),
             bar_current: String::new(),
             bar_current_n: String::new(),
             bar_remain: String::new(),
             bar_end: String::new(),
+            bar_buffered: BUFFERED_GLYPH.to_owned(),
+            buffered: 0,
+            buffered_color: None,
+            error_format: Vec::new(),
+            error_color: Some(Color::Red),
             tick: Vec::new(),
+            ascii_tick: Vec::new(),
             tick_state: 0,
             width: None,
             message: String::new(),
+            message_width: None,
+            truncation: Truncation::End,
+            fixed_box_width: true,
+            number_format: NumberFormat::default(),
+            percent_precision: 2,
             last_refresh_time: SteadyTime::now(),
             max_refresh_rate: None,
-            handle: handle,
+            paused_at: None,
+            paused_time: time::Duration::zero(),
+            rate_elapsed: time::Duration::zero(),
+            rate_current: 0.,
+            hide_cursor: true,
+            cursor_hidden: false,
+            finish_state: None,
+            drop_behavior: DropBehavior::Abandon,
+            non_tty: NonTtyBehavior::Draw,
+            caps: Capabilities::detect_handle(),
+            last_log: None,
+            log_pending: false,
+            leave_on_finish: true,
+            overflow: OverflowBehavior::Show,
+            multibar: None,
+            handle,
         };
         pb.format(FORMAT);
         pb.tick_format(TICK_FORMAT);
+        pb.ascii_tick_format(TICK_FORMAT);
+        pb.error_format(ERROR_FORMAT);
         pb
     }
 
@@ -156,242 +614,1619 @@ impl<T: Write> ProgressBar<T> {
         self.units = u;
     }
 
-    /// Set custom format to the drawing bar, default is `[=>-]`
+    /// Set the number of decimals of fractional progress values, default is 0.
+    ///
+    /// The counter box shows current and total with this number of
+    /// decimals. Values set with `set_f64`, `add_f64` and `set_total_f64`
+    /// keep their fractional part, while `add`, `inc`, `set` and `total`
+    /// stay in whole units.
     ///
     /// # Examples
     ///
-    /// ```ignore
-    /// let mut pb = ProgressBar::new(...);
-    /// pb.format("[=>_]");
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// // seconds of media transcoded
+    /// let mut pb = ProgressBar::new(0);
+    /// pb.set_precision(1);
+    /// pb.set_total_f64(312.5);
+    /// pb.set_f64(12.3);
     /// ```
-    pub fn format(&mut self, fmt: &str) {
-        if fmt.len() >= 5 {
-            let v: Vec<&str> = fmt.split("").collect();
-            self.bar_start = v[1].to_owned();
-            self.bar_current = v[2].to_owned();
-            self.bar_current_n = v[3].to_owned();
-            self.bar_remain = v[4].to_owned();
-            self.bar_end = v[5].to_owned();
-        }
+    pub fn set_precision(&mut self, precision: usize) {
+        self.precision = precision;
     }
 
-    /// Set message to display in the prefix, call with "" to stop printing a message.
+    // position is current with its fractional part.
+    fn position(&self) -> f64 {
+        self.current as f64 + self.current_frac
+    }
+
+    // total_f64 is total with its fractional part.
+    fn total_f64(&self) -> f64 {
+        self.total as f64 + self.total_frac
+    }
+
+    // set_position sets current and its fractional part to `v`.
+    fn set_position(&mut self, v: f64) {
+        let v = v.max(0.);
+        self.current = v.trunc() as u64;
+        self.current_frac = v.fract();
+    }
+
+    /// Add a named secondary counter, e.g. to count bytes next to files.
     ///
-    /// All newlines are replaced with spaces.
+    /// Secondary counters are drawn in the counter box after the main
+    /// counter, as "current / total name". Adding a counter with an
+    /// existing name replaces it.
     ///
     /// # Examples
-    /// ```ignore
-    /// let mut pb = ProgressBar::new(20);
     ///
-    /// for x in 0..20 {
-    ///    match x {
-    ///       0 => pb.message("Doing 1st Quarter"),
-    ///       5 => pb.message("Doing 2nd Quarter"),
-    ///       10 => pb.message("Doing 3rd Quarter"),
-    ///       15 => pb.message("Doing 4th Quarter"),
-    ///    }
-    ///    pb.inc().
-    /// }
+    /// ```no_run
+    /// use pbr::{ProgressBar, Units};
     ///
+    /// let mut pb = ProgressBar::new(900);
+    /// pb.add_counter("copied", 8_000_000_000, Units::Bytes);
+    /// pb.show_counter_speed("copied", true);
+    ///
+    /// // a file of 1.2 MB was copied
+    /// pb.counter_add("copied", 1_200_000);
+    /// pb.inc();
     /// ```
-    pub fn message(&mut self, message: &str) {
-        self.message = message.to_owned().replace("\n", " ").replace("\r", " ")
+    pub fn add_counter(&mut self, name: &str, total: u64, units: Units) {
+        self.counters.retain(|c| c.name != name);
+        self.counters.push(Counter {
+            name: name.to_owned(),
+            units,
+            current: 0,
+            total,
+            show_speed: false,
+            show_time_left: false,
+        });
     }
 
-    /// Set tick format for the progressBar, default is \\|/-
-    ///
-    /// Format is not limited to 4 characters, any string can
-    /// be used as a tick format (the tick will successively
-    /// take the value of each char but won't loop backwards).
+    /// Add to the current value of a secondary counter.
     ///
+    /// Returns the new value, or `None` if there is no such counter.
+    pub fn counter_add(&mut self, name: &str, i: u64) -> Option<u64> {
+        let current = self.counter_mut(name).map(|c| {
+            c.current += i;
+            c.current
+        });
+        self.draw();
+        current
+    }
+
+    /// Set the current value of a secondary counter.
     ///
-    /// # Examples
-    /// ```ignore
-    /// let mut pb = ProgressBar::new(...);
-    /// pb.tick_format("▀▐▄▌")
-    /// ```
-    pub fn tick_format(&mut self, tick_fmt: &str) {
-        if tick_fmt != TICK_FORMAT {
-            self.show_tick = true;
+    /// Returns the new value, or `None` if there is no such counter.
+    pub fn counter_set(&mut self, name: &str, i: u64) -> Option<u64> {
+        let current = self.counter_mut(name).map(|c| {
+            c.current = i;
+            c.current
+        });
+        self.draw();
+        current
+    }
+
+    /// Set the total of a secondary counter.
+    pub fn counter_set_total(&mut self, name: &str, total: u64) {
+        if let Some(c) = self.counter_mut(name) {
+            c.total = total;
         }
-        self.tick = tick_fmt.split("").map(|x| x.to_owned()).filter(|x| x != "").collect();
+        self.redraw();
     }
 
-    /// Set width, or `None` for default.
+    /// Set whether the speed of a secondary counter is drawn, default is `false`.
+    pub fn show_counter_speed(&mut self, name: &str, show: bool) {
+        if let Some(c) = self.counter_mut(name) {
+            c.show_speed = show;
+        }
+    }
+
+    /// Set whether the time left of a secondary counter is drawn, default
+    /// is `false`.
+    pub fn show_counter_time_left(&mut self, name: &str, show: bool) {
+        if let Some(c) = self.counter_mut(name) {
+            c.show_time_left = show;
+        }
+    }
+
+    /// Choose the secondary counter that drives the bar fill and the
+    /// percent box, or `None` for the main counter (the default).
     ///
     /// # Examples
     ///
     /// ```ignore
     /// let mut pb = ProgressBar::new(...);
-    /// pb.set_width(Some(80));
+    /// pb.add_counter("copied", n_bytes, Units::Bytes);
+    /// pb.fill_with_counter(Some("copied"));
     /// ```
-    pub fn set_width(&mut self, w: Option<usize>) {
-        self.width = w;
+    pub fn fill_with_counter(&mut self, name: Option<&str>) {
+        self.fill_counter = name.map(|n| n.to_owned());
     }
 
-    /// Set max refresh rate, above which the progress bar will not redraw, or `None` for none.
+    fn counter_mut(&mut self, name: &str) -> Option<&mut Counter> {
+        self.counters.iter_mut().find(|c| c.name == name)
+    }
+
+    // fill returns current and total of the counter driving the bar fill.
+    fn fill(&self) -> (f64, f64) {
+        self.fill_counter
+            .as_ref()
+            .and_then(|name| self.counters.iter().find(|c| c.name == *name))
+            .map_or((self.position(), self.total_f64()),
+                    |c| (c.current as f64, c.total as f64))
+    }
+
+    /// Add a category of items, drawn as its own segment of the bar, e.g.
+    /// to show how many tests passed, failed or were skipped.
+    ///
+    /// Segments are stacked in the order they were added, each drawn with
+    /// its own glyph and color. The counter box shows the tally of each
+    /// category. Adding a category with an existing name replaces it.
     ///
     /// # Examples
     ///
-    /// ```ignore
-    /// let mut pb = ProgressBar::new(...);
-    /// pb.set_max_refresh_rate(Some(Duration::from_millis(100)));
+    /// ```no_run
+    /// use pbr::{ProgressBar, Color};
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.add_segment("flaky", "?", Some(Color::Magenta));
+    /// pb.segment_add("flaky", 1);
     /// ```
-    pub fn set_max_refresh_rate(&mut self, w: Option<Duration>) {
-        self.max_refresh_rate = w.map(time::Duration::from_std).map(Result::unwrap);
-        if let Some(dur) = self.max_refresh_rate {
-            self.last_refresh_time = self.last_refresh_time - dur;
+    pub fn add_segment(&mut self, name: &str, glyph: &str, color: Option<Color>) {
+        self.segments.retain(|s| s.name != name);
+        self.segments.push(Segment {
+            name: name.to_owned(),
+            glyph: glyph.to_owned(),
+            color,
+            count: 0,
+        });
+    }
+
+    /// Add `i` items to the category `name`, and to current.
+    ///
+    /// The categories "ok", "err" and "skip" are added on first use if
+    /// needed, any other category must be added with `add_segment` first.
+    pub fn segment_add(&mut self, name: &str, i: u64) -> u64 {
+        if !self.segments.iter().any(|s| s.name == name) {
+            match name {
+                "ok" => self.add_segment(name, "=", Some(Color::Green)),
+                "err" => self.add_segment(name, "x", Some(Color::Red)),
+                "skip" => self.add_segment(name, "~", Some(Color::Yellow)),
+                _ => {}
+            }
+        }
+        if let Some(segment) = self.segments.iter_mut().find(|s| s.name == name) {
+            segment.count += i;
         }
+        self.add(i)
     }
 
-    /// Update progress bar even though no progress are made
-    /// Useful to see if a program is bricked or just
-    /// not doing any progress.
+    /// Increment the "ok" category, see `segment_add`.
+    pub fn inc_ok(&mut self) -> u64 {
+        self.segment_add("ok", 1)
+    }
+
+    /// Increment the "err" category, see `segment_add`.
+    pub fn inc_err(&mut self) -> u64 {
+        self.segment_add("err", 1)
+    }
+
+    /// Increment the "skip" category, see `segment_add`.
+    pub fn inc_skip(&mut self) -> u64 {
+        self.segment_add("skip", 1)
+    }
+
+    /// Attach `child` as a weighted part of this bar, e.g. one phase of a
+    /// pipeline.
     ///
-    /// tick is not needed with add or inc
-    /// as performed operation take place
-    /// in draw function.
+    /// Once a bar has children, its current value is driven by them: the
+    /// fraction done is the weighted average of the children's fractions,
+    /// so speed and time left are estimated from it as well. The child
+    /// reports its progress whenever it draws. It also redraws the parent
+    /// if the parent was shared with `into_shared`, otherwise call `tick()`
+    /// on the parent to redraw it.
     ///
     /// # Examples
-    /// ```ignore
-    /// let mut pb = ProgressBar::new(...);
-    /// pb.inc();
-    /// for _ in ... {
-    ///    ...do something
-    ///    pb.tick();
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// let mut scan = ProgressBar::new(20);
+    /// pb.add_child(&mut scan, 5.);
+    /// ```
+    pub fn add_child<W: Write>(&mut self, child: &mut ProgressBar<W>, weight: f64) {
+        let share = Arc::new(Mutex::new(ChildShare {
+            weight,
+            current: child.current,
+            total: child.total,
+            redraw: self.shared.clone(),
+        }));
+        child.parent = Some(share.clone());
+        self.children.push(share);
+    }
+
+    /// Create an invisible child bar, see `add_child`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// let mut scan = pb.create_child(5., 20);
+    /// let mut download = pb.create_child(80., 1024);
+    /// let mut verify = pb.create_child(15., 1024);
+    /// for _ in 0..20 {
+    ///     scan.inc();
+    ///     pb.tick();
     /// }
-    /// pb.finish();
+    /// // ...
     /// ```
-    pub fn tick(&mut self) {
-        self.tick_state = (self.tick_state + 1) % self.tick.len();
-        if self.current <= self.total {
-            self.draw()
+    pub fn create_child(&mut self, weight: f64, total: u64) -> ProgressBar<Sink> {
+        let mut child = ProgressBar::on(::std::io::sink(), total);
+        child.hide_cursor = false;
+        child.drop_behavior = DropBehavior::Leave;
+        self.add_child(&mut child, weight);
+        child
+    }
+
+    // roll_up sets current from the progress of the children.
+    fn roll_up(&mut self) {
+        if self.children.is_empty() {
+            return;
+        }
+        let (mut done, mut weights) = (0., 0.);
+        for child in &self.children {
+            let child = child.lock().unwrap();
+            done += child.weight * child.fraction();
+            weights += child.weight;
+        }
+        if weights > 0. {
+            self.current = (done / weights * self.total as f64).round() as u64;
+            self.current_frac = 0.;
         }
     }
 
-    /// Add to current value
+    // sync_parent reports the progress to the parent bar, if any, and
+    // redraws the parent if it is shared.
+    fn sync_parent(&self) {
+        let parent = match self.parent {
+            Some(ref share) => {
+                let mut share = share.lock().unwrap();
+                share.current = self.current;
+                share.total = self.total;
+                share.redraw.as_ref().and_then(|p| p.upgrade())
+            }
+            None => None,
+        };
+        // skip the redraw if the parent is locked, e.g. by the thread
+        // drawing this bar
+        if let Some(parent) = parent {
+            if let Ok(mut parent) = parent.try_lock() {
+                parent.redraw_for_child();
+            }
+        }
+    }
+
+    /// Set the names of the stages the bar goes through, e.g. "Fetching",
+    /// "Compiling", "Linking". Start the first one with `next_stage`.
+    ///
+    /// While a stage runs, the bar is prefixed with "[i/n] name".
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use pbr::ProgressBar;
     ///
-    /// let mut pb = ProgressBar::new(10);
-    /// pb.add(5);
-    /// pb.finish();
+    /// let mut pb = ProgressBar::new(0);
+    /// pb.set_stages(&["Fetching", "Compiling"]);
+    ///
+    /// pb.next_stage(3);
+    /// for _ in 0..3 {
+    ///     pb.inc();
+    /// }
+    /// pb.next_stage(10);
+    /// for _ in 0..10 {
+    ///     pb.inc();
+    /// }
+    /// pb.finish_println("");
+    /// println!("{}", pb.stage_report());
     /// ```
-    pub fn add(&mut self, i: u64) -> u64 {
-        self.current += i;
-        self.tick();
-        self.current
+    pub fn set_stages(&mut self, stages: &[&str]) {
+        self.stages = stages.iter().map(|s| (*s).to_owned()).collect();
+        self.stage = None;
+        self.stage_times.clear();
     }
 
-    /// Manually set the current value of the bar
-    /// 
+    /// Complete the current stage (if any) and start the next one with
+    /// `total` as total, resetting the bar.
+    ///
+    /// Returns `false` if there is no next stage.
+    pub fn next_stage(&mut self, total: u64) -> bool {
+        self.end_stage();
+        let next = self.stage.map_or(0, |i| i + 1);
+        if next >= self.stages.len() {
+            return false;
+        }
+        self.reset();
+        self.stage = Some(next);
+        self.set_total(total);
+        true
+    }
+
+    /// Get the names and durations of the completed stages.
+    ///
+    /// The current stage completes when the bar finishes.
+    pub fn stage_times(&self) -> Vec<(&str, Duration)> {
+        self.stages
+            .iter()
+            .zip(self.stage_times.iter())
+            .map(|(name, d)| (&name[..], *d))
+            .collect()
+    }
+
+    /// Get a report of the completed stages, one line per stage like
+    /// "[1/2] Fetching 1.20s".
+    pub fn stage_report(&self) -> String {
+        let n = self.stages.len();
+        self.stage_times()
+            .iter()
+            .enumerate()
+            .map(|(i, &(name, d))| format!("[{}/{}] {} {:.2}s", i + 1, n, name, fract_dur(d)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Get summary statistics of the run so far, or of the whole run once
+    /// the bar finished.
+    ///
     /// # Examples
+    ///
     /// ```no_run
     /// use pbr::ProgressBar;
-    /// 
+    ///
     /// let mut pb = ProgressBar::new(10);
-    /// pb.set(8);
+    /// pb.add(10);
     /// pb.finish();
-    pub fn set(&mut self, i: u64) -> u64 {
-        self.current = i;
-        self.tick();
-        self.current
+    /// let stats = pb.stats();
+    /// println!("\ntook {:?}, stalled {:?}", stats.elapsed, stats.stalled);
+    /// ```
+    pub fn stats(&self) -> Stats {
+        let elapsed = self.stats.end.unwrap_or_else(|| self.elapsed(SteadyTime::now()));
+        let secs = fract_dur(time_to_std(elapsed));
+        let average_speed = if secs > 0. {
+            self.position() / secs
+        } else {
+            0.
+        };
+        Stats {
+            elapsed: time_to_std(elapsed),
+            average_speed,
+            peak_speed: self.stats.peak_speed.max(average_speed),
+            stalled: time_to_std(self.stats.stalled),
+        }
     }
 
-    /// Increment current value
-    pub fn inc(&mut self) -> u64 {
-        self.add(1)
+    /// Get a one line summary of `stats()`, like
+    /// "done in 12.30s, 1.20 MB/s average, 2.30 MB/s peak, stalled 0.00s".
+    /// It starts with "failed after", "abandoned after" or "running for"
+    /// if the bar did not finish successfully.
+    pub fn stats_summary(&self) -> String {
+        let stats = self.stats();
+        let state = match self.finish_state {
+            Some(FinishState::Success) => "done in",
+            Some(FinishState::Error(_)) => "failed after",
+            Some(FinishState::Abandoned(_)) => "abandoned after",
+            None => "running for",
+        };
+        format!("{} {:.2}s, {} average, {} peak, stalled {:.2}s",
+                state,
+                fract_dur(stats.elapsed),
+                fmt_speed(&self.units, stats.average_speed, &self.number_format),
+                fmt_speed(&self.units, stats.peak_speed, &self.number_format),
+                fract_dur(stats.stalled))
     }
 
-    fn draw(&mut self) {
-        let now = SteadyTime::now();
-        if let Some(mrr) = self.max_refresh_rate {
-            if now - self.last_refresh_time < mrr {
-                return;
+    // end_stage records the duration of the current stage, once.
+    fn end_stage(&mut self) {
+        if let Some(i) = self.stage {
+            if self.stage_times.len() == i {
+                let elapsed = self.elapsed(SteadyTime::now());
+                self.stage_times.push(time_to_std(elapsed));
             }
         }
+    }
+
+    /// Set custom format to the drawing bar, default is `[=>-]`
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.format("[=>_]");
+    /// ```
+    pub fn format(&mut self, fmt: &str) {
+        let v = glyphs(fmt);
+        if v.len() >= 5 {
+            self.bar_start = v[0].to_owned();
+            self.bar_current = v[1].to_owned();
+            self.bar_current_n = v[2].to_owned();
+            self.bar_remain = v[3].to_owned();
+            self.bar_end = v[4].to_owned();
+        }
+    }
+
+    /// Set the bar glyphs used instead of a Unicode `format` when the output
+    /// cannot render Unicode, default is `[=>-]`. See `set_ascii`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.format("╢▌▌░╟");
+    /// pb.ascii_format("|##.|");
+    /// ```
+    pub fn ascii_format(&mut self, fmt: &str) {
+        if fmt.chars().count() >= 5 && fmt.is_ascii() {
+            self.ascii_format = fmt.to_owned();
+        }
+    }
+
+    /// Set the secondary position of the bar, e.g. how far a download got
+    /// ahead of decoding.
+    ///
+    /// The cells between current and the buffered position are drawn with
+    /// the buffered glyph (see `set_buffered_glyph`) and color.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.set_buffered(60);
+    /// pb.set(20);
+    /// ```
+    pub fn set_buffered(&mut self, pos: u64) -> u64 {
+        self.buffered = pos;
+        self.draw();
+        self.buffered
+    }
+
+    /// Add to the buffered position, see `set_buffered`.
+    pub fn buffered_add(&mut self, i: u64) -> u64 {
+        let pos = self.buffered + i;
+        self.set_buffered(pos)
+    }
+
+    /// Set the glyph the buffered part of the bar is drawn with, default is
+    /// `~`. Anything but a single glyph is ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.format("[=>-]");
+    /// pb.set_buffered_glyph("+");
+    /// ```
+    pub fn set_buffered_glyph(&mut self, glyph: &str) {
+        if glyph.chars().count() == 1 {
+            self.bar_buffered = glyph.to_owned();
+        }
+    }
+
+    /// Set the color of the buffered part of the bar, or `None` for no
+    /// color (the default).
+    ///
+    /// Colors are only used if the output supports them, see
+    /// `Capabilities::color`.
+    pub fn set_buffered_color(&mut self, color: Option<Color>) {
+        self.buffered_color = color;
+    }
+
+    /// Set custom format to draw the bar with when it finished with
+    /// `finish_with_error`, default is `[=X-]`
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.error_format("[#!_]");
+    /// ```
+    pub fn error_format(&mut self, fmt: &str) {
+        let v = glyphs(fmt);
+        if v.len() >= 5 {
+            self.error_format = v.iter().take(5).map(|x| (*x).to_owned()).collect();
+        }
+    }
+
+    /// Set the color of the bar when it finished with `finish_with_error`,
+    /// or `None` for no color. Default is `Some(Color::Red)`.
+    ///
+    /// Colors are only used if the output supports them, see
+    /// `Capabilities::color`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_error_color(Some(Color::Magenta));
+    /// ```
+    pub fn set_error_color(&mut self, color: Option<Color>) {
+        self.error_color = color;
+    }
+
+    /// Set message to display in the prefix, call with "" to stop printing a message.
+    ///
+    /// All newlines are replaced with spaces.
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(20);
+    ///
+    /// for x in 0..20 {
+    ///    match x {
+    ///       0 => pb.message("Doing 1st Quarter"),
+    ///       5 => pb.message("Doing 2nd Quarter"),
+    ///       10 => pb.message("Doing 3rd Quarter"),
+    ///       15 => pb.message("Doing 4th Quarter"),
+    ///    }
+    ///    pb.inc().
+    /// }
+    ///
+    /// ```
+    pub fn message(&mut self, message: &str) {
+        self.message = message.to_owned().replace("\n", " ").replace("\r", " ")
+    }
+
+    /// Set the layout of the progress bar line, the order of its boxes.
+    ///
+    /// Boxes are written as `{name}`, see `Builtin` for the names of the
+    /// built-in ones, other names refer to widgets registered with
+    /// `add_widget`. Any other text is printed as is. The `show_*` flags
+    /// still hide the built-in boxes. The default layout is
+    /// `{stage}{message}{counter}{counters}{tick}{bar}{percent}{speed}{time_left}{paused}`.
+    /// Keep `{paused}` in a custom layout to show when the bar is paused.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.set_layout("{message}{bar} {counter}| {time_left}{paused}");
+    /// ```
+    pub fn set_layout(&mut self, layout: &str) {
+        self.layout = parse_layout(layout);
+    }
+
+    /// Set the minimum width of the bar, default is 10.
+    ///
+    /// When the line does not fit the terminal, the other boxes are
+    /// shortened or dropped by priority (see `Widget::priority`) to keep at
+    /// least this many cells for the bar.
+    pub fn set_bar_min_width(&mut self, width: usize) {
+        self.bar_min_width = width;
+    }
+
+    /// Register a widget under a name, to be used as `{name}` in the layout.
+    ///
+    /// Registering a widget with an existing name replaces it, a widget named
+    /// after a built-in box (see `Builtin`) replaces that box.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{ProgressBar, ProgressState};
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.add_widget("left", |state: &ProgressState, _: usize| {
+    ///     format!("{} left ", state.total - state.position)
+    /// });
+    /// pb.set_layout("{left}{bar}");
+    /// ```
+    pub fn add_widget<W: Widget + 'static>(&mut self, name: &str, widget: W) {
+        self.widgets.retain(|w| w.0 != name);
+        self.widgets.push((name.to_owned(), Box::new(widget)));
+    }
+
+    /// Add a widget to the prefix, right before the bar.
+    pub fn add_prefix_widget<W: Widget + 'static>(&mut self, widget: W) {
+        let bar = self.layout
+            .iter()
+            .position(|slot| matches!(*slot, Slot::Builtin(Builtin::Bar)))
+            .unwrap_or(self.layout.len());
+        self.layout.insert(bar, Slot::Widget(Box::new(widget)));
+    }
+
+    /// Add a widget to the suffix, at the end of the line.
+    pub fn add_suffix_widget<W: Widget + 'static>(&mut self, widget: W) {
+        self.layout.push(Slot::Widget(Box::new(widget)));
+    }
+
+    /// Give the message box a fixed width, so the bar does not move when
+    /// the message changes. Longer messages are shortened as set with
+    /// `set_truncation`, shorter ones padded with spaces.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{ProgressBar, Truncation};
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.set_message_width(Some(30));
+    /// pb.set_truncation(Truncation::Path);
+    /// pb.message("/home/user/projects/very_long_name.rs ");
+    /// ```
+    pub fn set_message_width(&mut self, width: Option<usize>) {
+        self.message_width = width;
+    }
+
+    /// Set how a message that is too long is shortened, both for the
+    /// `set_message_width` column and on narrow terminals, default is
+    /// `Truncation::End`.
+    pub fn set_truncation(&mut self, truncation: Truncation) {
+        self.truncation = truncation;
+    }
+
+    /// Pad the counter, percent and speed boxes to stable widths, so the
+    /// bar does not move as the numbers grow, default is true.
+    ///
+    /// The current value is as wide as the total, the percentage as wide as
+    /// "100.00", and the speed as wide as "1023.99 KB/s".
+    pub fn set_fixed_box_width(&mut self, fixed: bool) {
+        self.fixed_box_width = fixed;
+    }
+
+    /// Set how numbers are written in the counter and speed boxes, see
+    /// `NumberFormat`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{ProgressBar, NumberFormat};
+    ///
+    /// let mut pb = ProgressBar::new(10_000_000);
+    /// pb.set_number_format(NumberFormat::locale("en_US"));
+    /// // 1,234,567 / 10,000,000
+    /// pb.set(1_234_567);
+    /// ```
+    pub fn set_number_format(&mut self, format: NumberFormat) {
+        self.number_format = format;
+    }
+
+    /// Set the number of decimals of the percent box, default is 2.
+    pub fn set_percent_precision(&mut self, precision: usize) {
+        self.percent_precision = precision;
+    }
+
+    /// Set tick format for the progressBar, default is \\|/-
+    ///
+    /// Format is not limited to 4 characters, any string can
+    /// be used as a tick format (the tick will successively
+    /// take the value of each char but won't loop backwards).
+    ///
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.tick_format("▀▐▄▌")
+    /// ```
+    pub fn tick_format(&mut self, tick_fmt: &str) {
+        if tick_fmt != TICK_FORMAT {
+            self.show_tick = true;
+        }
+        self.tick = glyphs(tick_fmt).into_iter().map(|x| x.to_owned()).collect();
+    }
+
+    /// Set the spinner frames used instead of a Unicode `tick_format` when
+    /// the output cannot render Unicode, default is \\|/-. See `set_ascii`.
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.tick_format("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏");
+    /// pb.ascii_tick_format(".oOo");
+    /// ```
+    pub fn ascii_tick_format(&mut self, tick_fmt: &str) {
+        if !tick_fmt.is_empty() && tick_fmt.is_ascii() {
+            self.ascii_tick = glyphs(tick_fmt).into_iter().map(|x| x.to_owned()).collect();
+        }
+    }
+
+    /// Apply a style: the bar glyphs, spinner frames, visible boxes, units
+    /// and layout, and the colors, refresh rate and non-TTY behavior the
+    /// style sets, see `ProgressStyle`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{ProgressBar, ProgressStyle};
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// pb.set_style(&ProgressStyle::wget());
+    /// ```
+    pub fn set_style(&mut self, style: &ProgressStyle) {
+        self.format(&style.format);
+        self.ascii_format(&style.ascii_format);
+        self.tick_format(&style.tick_format);
+        self.ascii_tick_format(&style.ascii_tick_format);
+        self.set_buffered_glyph(&style.buffered_glyph);
+        self.set_layout(&style.layout);
+        self.set_units(style.units);
+        self.show_bar = style.show_bar;
+        self.show_speed = style.show_speed;
+        self.show_percent = style.show_percent;
+        self.show_counter = style.show_counter;
+        self.show_time_left = style.show_time_left;
+        self.show_tick = style.show_tick;
+        self.show_message = style.show_message;
+        if style.error_color.is_some() {
+            self.set_error_color(style.error_color);
+        }
+        if style.buffered_color.is_some() {
+            self.set_buffered_color(style.buffered_color);
+        }
+        if style.max_refresh_rate.is_some() {
+            self.set_max_refresh_rate(style.max_refresh_rate);
+        }
+        if let Some(non_tty) = style.non_tty {
+            self.set_non_tty(non_tty);
+        }
+    }
+
+    /// Set width, or `None` for default.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_width(Some(80));
+    /// ```
+    pub fn set_width(&mut self, w: Option<usize>) {
+        self.width = w;
+    }
+
+    /// Set max refresh rate, above which the progress bar will not redraw, or `None` for none.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_max_refresh_rate(Some(Duration::from_millis(100)));
+    /// ```
+    pub fn set_max_refresh_rate(&mut self, w: Option<Duration>) {
+        self.max_refresh_rate = w.map(time::Duration::from_std).map(Result::unwrap);
+        if let Some(dur) = self.max_refresh_rate {
+            self.last_refresh_time = self.last_refresh_time - dur;
+        }
+    }
+
+    /// Set whether the cursor should be hidden while the bar is drawing,
+    /// default is `true`.
+    ///
+    /// The cursor is only hidden if the output is a terminal, see
+    /// `Capabilities::terminal`, and it is shown again when the bar
+    /// finishes or is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_hide_cursor(false);
+    /// ```
+    pub fn set_hide_cursor(&mut self, hide: bool) {
+        self.hide_cursor = hide;
+    }
+
+    /// Set what the bar does when its output is not a terminal, default is
+    /// `NonTtyBehavior::Draw`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_non_tty(NonTtyBehavior::Hide);
+    /// ```
+    pub fn set_non_tty(&mut self, behavior: NonTtyBehavior) {
+        self.non_tty = behavior;
+    }
+
+    /// Set what the output can render, detected from the environment by
+    /// default, see `Capabilities::detect`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_capabilities(Capabilities { redraw: false, ..pb.capabilities() });
+    /// ```
+    pub fn set_capabilities(&mut self, caps: Capabilities) {
+        self.caps = caps;
+    }
+
+    /// Force the ASCII glyphs (see `ascii_format`, `ascii_tick_format`) and
+    /// "..." for shortened messages, or force Unicode, whatever the locale
+    /// says. Shorthand for `set_capabilities` with `unicode` set.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_ascii(true);
+    /// ```
+    pub fn set_ascii(&mut self, ascii: bool) {
+        let caps = Capabilities { unicode: !ascii, ..self.caps };
+        self.set_capabilities(caps);
+    }
+
+    /// Get what the output can render, see `set_capabilities`.
+    pub fn capabilities(&self) -> Capabilities {
+        self.caps
+    }
+
+    /// Set what the bar does when it is dropped before it finished,
+    /// default is `DropBehavior::Abandon`.
+    ///
+    /// Unless the behavior is `DropBehavior::Leave`, a standalone bar also
+    /// ends its line, so following output starts on a new line.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_drop_behavior(DropBehavior::Clear);
+    /// ```
+    pub fn set_drop_behavior(&mut self, behavior: DropBehavior) {
+        self.drop_behavior = behavior;
+    }
+
+    /// Set whether the bar stays on screen when it finishes, default is `true`.
+    ///
+    /// With `false`, `finish()` behaves like `finish_and_clear()`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.leave_on_finish(false);
+    /// ```
+    pub fn leave_on_finish(&mut self, leave: bool) {
+        self.leave_on_finish = leave;
+    }
+
+    /// Set what the bar does when current goes above total, default is
+    /// `OverflowBehavior::Show`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.set_overflow(OverflowBehavior::Grow);
+    /// ```
+    pub fn set_overflow(&mut self, overflow: OverflowBehavior) {
+        self.overflow = overflow;
+        self.check_overflow();
+    }
+
+    /// Set total, and redraw the bar immediately.
+    ///
+    /// Useful when the amount of work is discovered on the go, e.g. when
+    /// walking a directory tree. The speed and time left keep being
+    /// estimated from the progress made so far.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.add(5);
+    /// pb.set_total(20);
+    /// ```
+    pub fn set_total(&mut self, total: u64) {
+        self.total = total;
+        self.total_frac = 0.;
+        self.check_overflow();
+        self.redraw();
+    }
+
+    /// Add to total, and redraw the bar immediately.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.inc_total(5);
+    /// ```
+    pub fn inc_total(&mut self, i: u64) {
+        self.total += i;
+        self.check_overflow();
+        self.redraw();
+    }
 
-        let time_elapsed = time_to_std(now - self.start_time);
-        let speed = self.current as f64 / fract_dur(time_elapsed);
-        let width = self.width();
+    // check_overflow applies the overflow behavior if current is above total.
+    fn check_overflow(&mut self) {
+        if self.position() > self.total_f64() {
+            match self.overflow {
+                OverflowBehavior::Clamp => {
+                    self.current = self.total;
+                    self.current_frac = self.total_frac;
+                }
+                OverflowBehavior::Grow => {
+                    self.total = self.current;
+                    self.total_frac = self.current_frac;
+                }
+                OverflowBehavior::Show => {}
+            }
+        }
+    }
 
-        let mut base = String::new();
-        let mut suffix = String::new();
-        let mut prefix = String::new();
-        let mut out;
-
-        // precent box
-        if self.show_percent {
-            let percent = self.current as f64 / (self.total as f64 / 100f64);
-            suffix = suffix +
-                     &format!(" {:.*} % ", 2, if percent.is_nan() { 0.0 } else { percent });
-        }
-        // speed box
-        if self.show_speed {
-            suffix = match self.units {
-                Units::Default => suffix + &format!("{:.*}/s ", 2, speed),
-                Units::Bytes => suffix + &format!("{}/s ", kb_fmt!(speed)),
-            };
+    /// Update progress bar even though no progress are made
+    /// Useful to see if a program is bricked or just
+    /// not doing any progress.
+    ///
+    /// tick is not needed with add or inc
+    /// as performed operation take place
+    /// in draw function.
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let mut pb = ProgressBar::new(...);
+    /// pb.inc();
+    /// for _ in ... {
+    ///    ...do something
+    ///    pb.tick();
+    /// }
+    /// pb.finish();
+    /// ```
+    pub fn tick(&mut self) {
+        if self.paused_at.is_none() {
+            self.tick_state = self.tick_state.wrapping_add(1);
+        }
+        self.draw()
+    }
+
+    /// Add to current value
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.add(5);
+    /// pb.finish();
+    /// ```
+    pub fn add(&mut self, i: u64) -> u64 {
+        self.current += i;
+        self.check_overflow();
+        self.tick();
+        self.current
+    }
+
+    /// Manually set the current value of the bar
+    /// 
+    /// # Examples
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    /// 
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.set(8);
+    /// pb.finish();
+    pub fn set(&mut self, i: u64) -> u64 {
+        self.current = i;
+        self.current_frac = 0.;
+        self.check_overflow();
+        self.tick();
+        self.current
+    }
+
+    /// Manually set the current value of the bar as fractional value, see
+    /// `set_precision`.
+    pub fn set_f64(&mut self, v: f64) -> f64 {
+        self.set_position(v);
+        self.check_overflow();
+        self.tick();
+        self.position()
+    }
+
+    /// Add fractional value to current, see `set_precision`.
+    pub fn add_f64(&mut self, v: f64) -> f64 {
+        let v = self.position() + v;
+        self.set_f64(v)
+    }
+
+    /// Set total as fractional value, see `set_precision`.
+    pub fn set_total_f64(&mut self, total: f64) {
+        let total = total.max(0.);
+        self.total = total.trunc() as u64;
+        self.total_frac = total.fract();
+        self.check_overflow();
+        self.redraw();
+    }
+
+    /// Set current to the given fraction of total, e.g. `0.25` for a
+    /// quarter done.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(1000);
+    /// // a subprocess reported 42.5%
+    /// pb.set_fraction(0.425);
+    /// ```
+    pub fn set_fraction(&mut self, fraction: f64) -> u64 {
+        let v = (self.total_f64() * fraction).round().max(0.) as u64;
+        self.set(v)
+    }
+
+    /// Increment current value
+    pub fn inc(&mut self) -> u64 {
+        self.add(1)
+    }
+
+    /// Subtract from current value, e.g. when a chunk has to be retried
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.add(5);
+    /// pb.sub(2);
+    /// ```
+    pub fn sub(&mut self, i: u64) -> u64 {
+        let v = self.position() - i as f64;
+        self.set_position(v);
+        self.tick();
+        self.current
+    }
+
+    /// Decrement current value
+    pub fn dec(&mut self) -> u64 {
+        self.sub(1)
+    }
+
+    /// Move current to `pos`, and restart estimating the speed and time
+    /// left from there.
+    ///
+    /// Unlike `set`, progress made before the rewind doesn't count towards
+    /// the speed anymore.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.add(8);
+    /// // the transfer broke, resume it from the last checkpoint
+    /// pb.rewind_to(5);
+    /// ```
+    pub fn rewind_to(&mut self, pos: u64) -> u64 {
+        self.current = pos;
+        self.current_frac = 0.;
+        self.check_overflow();
+        self.rate_elapsed = self.elapsed(SteadyTime::now());
+        self.rate_current = self.position();
+        self.redraw();
+        self.current
+    }
+
+    /// Reset the bar to its initial state, so it can be reused, e.g. for
+    /// several files copied one after another.
+    ///
+    /// Current (also of secondary counters and segments), the start time and
+    /// the finish state are reset, the configuration (including total and
+    /// message) is kept.
+    ///
+    /// A bar of a MultiBar must not be reset after it finished.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.add(10);
+    /// pb.finish_println("first file done");
+    /// pb.reset();
+    /// pb.set_total(20);
+    /// ```
+    pub fn reset(&mut self) {
+        let now = SteadyTime::now();
+        self.current = 0;
+        self.current_frac = 0.;
+        self.buffered = 0;
+        self.start_time = now;
+        self.paused_at = None;
+        self.paused_time = time::Duration::zero();
+        self.rate_elapsed = time::Duration::zero();
+        self.rate_current = 0.;
+        for counter in &mut self.counters {
+            counter.current = 0;
+        }
+        for segment in &mut self.segments {
+            segment.count = 0;
+        }
+        self.tick_state = 0;
+        self.stats = StatsTracker::new();
+        self.finish_state = None;
+        self.is_finish = false;
+        if let Some(mrr) = self.max_refresh_rate {
+            self.last_refresh_time = now - mrr;
+        }
+    }
+
+    /// Pause the bar, e.g. while waiting for user input.
+    ///
+    /// The time until `resume()` is called doesn't count towards the
+    /// elapsed time, so it doesn't lower the speed or inflate the time left.
+    /// While paused the bar shows "paused" (see `Builtin::Paused`) instead
+    /// of the time left, and the tick doesn't move.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.inc();
+    /// pb.pause();
+    /// // ...ask the user something
+    /// pb.resume();
+    /// pb.inc();
+    /// ```
+    pub fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(SteadyTime::now());
+            self.redraw();
+        }
+    }
+
+    /// Resume the bar after `pause()`.
+    pub fn resume(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused_time = self.paused_time + (SteadyTime::now() - paused_at);
+            self.redraw();
+        }
+    }
+
+    /// Whether the bar is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    // elapsed returns the time since the bar started, without the time
+    // it was paused.
+    fn elapsed(&self, now: SteadyTime) -> time::Duration {
+        let paused = match self.paused_at {
+            Some(paused_at) => self.paused_time + (now - paused_at),
+            None => self.paused_time,
+        };
+        now - self.start_time - paused
+    }
+
+    // speed is the current speed in units per second, measured since the
+    // start or the last rewind.
+    fn speed(&self, elapsed: time::Duration) -> f64 {
+        // a rewound finished bar measures from after its end
+        let window = elapsed - self.rate_elapsed;
+        if window > time::Duration::zero() {
+            let time_elapsed = fract_dur(time_to_std(window));
+            (self.position() - self.rate_current).max(0.) / time_elapsed
+        } else {
+            0.
+        }
+    }
+
+    // time_left estimates the seconds left at the given speed.
+    fn time_left(&self, speed: f64) -> Option<f64> {
+        let left = self.total_f64() - self.position();
+        if speed > 0. && left > 0. {
+            Some(1. / speed * left)
+        } else {
+            None
+        }
+    }
+
+    /// Get a snapshot of the current state of the progress bar, e.g. to
+    /// mirror it into another UI.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.add(5);
+    /// let state = pb.state();
+    /// assert_eq!(state.fraction, 0.5);
+    /// ```
+    pub fn state(&self) -> ProgressState {
+        let elapsed = self.stats.end.unwrap_or_else(|| self.elapsed(SteadyTime::now()));
+        let speed = self.speed(elapsed);
+        let eta = if self.paused_at.is_some() || self.is_finish {
+            None
+        } else {
+            self.time_left(speed).map(|left| {
+                Duration::new(left as u64, (left.fract() * NANOS_PER_SEC as f64) as u32)
+            })
+        };
+        let (fill_current, fill_total) = self.fill();
+        let fraction = fill_current / fill_total;
+        ProgressState {
+            position: self.position(),
+            total: self.total_f64(),
+            fraction: if fraction.is_nan() { 0. } else { fraction },
+            elapsed: time_to_std(elapsed),
+            rate: speed,
+            eta,
+            message: self.message.clone(),
+            paused: self.paused_at.is_some(),
+            finish_state: self.finish_state.clone(),
         }
-        // time left box
-        if self.show_time_left && self.current > 0 {
-            if self.total > self.current {
-                let left = 1. / speed * (self.total - self.current) as f64;
-                if left < 60. {
-                    suffix = suffix + &format!("{:.0}s", left);
+    }
+
+    // boxes prepares the built-in boxes for a draw.
+    fn boxes(&self, elapsed: time::Duration, speed: f64) -> Boxes {
+        Boxes {
+            text: Builtin::ALL
+                .iter()
+                .filter(|b| **b != Builtin::Bar)
+                .map(|b| (*b, self.render_box(*b, elapsed, speed)))
+                .collect(),
+            truncation: self.truncation,
+            ellipsis: self.ellipsis(),
+            bar: if self.show_bar { Some(self.bar_box()) } else { None },
+        }
+    }
+
+    // slot_widget finds the widget drawing a box of the layout. A widget
+    // registered under the name of a built-in box replaces it.
+    fn slot_widget<'a>(&'a self,
+                       slot: &'a Slot,
+                       builtins: &'a [BuiltinBox<'a>])
+                       -> Option<&'a (dyn Widget + 'a)> {
+        let named = |name: &str| {
+            self.widgets.iter().find(|w| w.0 == name).map(|w| &*w.1 as &dyn Widget)
+        };
+        match *slot {
+            Slot::Text(_) => None,
+            Slot::Builtin(b) => {
+                named(b.name())
+                    .or_else(|| builtins.iter().find(|w| w.builtin == b).map(|w| w as &dyn Widget))
+            }
+            Slot::Named(ref name) => named(name),
+            Slot::Widget(ref widget) => Some(&**widget),
+        }
+    }
+
+    // render_box renders one of the built-in boxes, except for the bar.
+    fn render_box(&self, b: Builtin, elapsed: time::Duration, speed: f64) -> String {
+        match b {
+            Builtin::Percent if self.show_percent => {
+                let (fill_current, fill_total) = self.fill();
+                let percent = fill_current / (fill_total / 100f64);
+                let p = self.percent_precision;
+                let percent = self.number_format
+                    .format(if percent.is_nan() { 0.0 } else { percent }, p);
+                // as wide as "100.00"
+                let w = if !self.fixed_box_width {
+                    0
+                } else if p > 0 {
+                    4 + p
+                } else {
+                    3
+                };
+                format!(" {:>w$} % ", percent, w = w)
+            }
+            Builtin::Speed if self.show_speed => {
+                let nf = &self.number_format;
+                let mut out = fmt_speed(&self.units, speed, nf);
+                if self.fixed_box_width {
+                    if out.chars().count() > SPEED_WIDTH {
+                        out = fmt_number(&self.units, speed, 0, nf) + "/s";
+                    }
+                    out = format!("{:>w$}", out, w = SPEED_WIDTH);
+                }
+                out + " "
+            }
+            Builtin::TimeLeft if self.paused_at.is_none() => {
+                self.time_left(speed)
+                    .filter(|_| self.show_time_left)
+                    .map_or_else(String::new, fmt_time_left)
+            }
+            Builtin::Paused if self.paused_at.is_some() => "paused".to_owned(),
+            Builtin::Stage => {
+                self.stage.map_or_else(String::new, |i| {
+                    format!("[{}/{}] {} ", i + 1, self.stages.len(), self.stages[i])
+                })
+            }
+            Builtin::Message if self.show_message => {
+                match self.message_width {
+                    Some(width) => {
+                        let message = truncate_message(&self.message,
+                                                       width,
+                                                       self.truncation,
+                                                       self.ellipsis());
+                        let pad = width - message.chars().count();
+                        message + repeat!(" ", pad)
+                    }
+                    None => self.message.clone(),
+                }
+            }
+            Builtin::Counter if self.show_counter => {
+                let (c, t) = (self.position(), self.total_f64());
+                let (p, nf) = (self.precision, &self.number_format);
+                let (cs, ts) = (fmt_number(&self.units, c, p, nf),
+                                fmt_number(&self.units, t, p, nf));
+                // keep current as wide as total
+                let w = if self.fixed_box_width { ts.chars().count() } else { 0 };
+                let mut out = format!("{:>w$} / {} ", cs, ts, w = w);
+                if c > t {
+                    let over = c - t;
+                    out += &format!("(+{} over) ", fmt_number(&self.units, over, p, nf));
+                }
+                // category tallies
+                if !self.segments.is_empty() {
+                    let tallies: Vec<String> = self.segments
+                        .iter()
+                        .map(|s| format!("{} {}", s.count, s.name))
+                        .collect();
+                    out += &format!("({}) ", tallies.join(", "));
+                }
+                out
+            }
+            Builtin::Counters if self.show_counter => {
+                let mut out = String::new();
+                let nf = &self.number_format;
+                let time_elapsed = fract_dur(time_to_std(elapsed));
+                for counter in &self.counters {
+                    let (c, t) = (counter.current as f64, counter.total as f64);
+                    out += &format!("{} / {} {} ",
+                                    fmt_number(&counter.units, c, 0, nf),
+                                    fmt_number(&counter.units, t, 0, nf),
+                                    counter.name);
+                    let speed = if time_elapsed > 0. { c / time_elapsed } else { 0. };
+                    let mut rate = Vec::new();
+                    if counter.show_speed {
+                        rate.push(fmt_speed(&counter.units, speed, nf));
+                    }
+                    if counter.show_time_left && speed > 0. && t > c {
+                        rate.push(fmt_time_left((t - c) / speed));
+                    }
+                    if !rate.is_empty() {
+                        out += &format!("({}) ", rate.join(" "));
+                    }
+                }
+                out
+            }
+            Builtin::Tick if self.show_tick => {
+                let frames = if self.drawable(&self.tick) {
+                    &self.tick
                 } else {
-                    suffix = suffix + &format!("{:.0}m", left / 60.);
+                    &self.ascii_tick
+                };
+                match frames.len() {
+                    0 => String::new(),
+                    n => format!("{} ", frames[self.tick_state % n]),
                 }
             }
+            _ => String::new(),
         }
-        // message box
-        if self.show_message {
-            prefix = prefix + &format!("{}", self.message)
-        }
-        // counter box
-        if self.show_counter {
-            let (c, t) = (self.current as f64, self.total as f64);
-            prefix = prefix +
-                     &match self.units {
-                Units::Default => format!("{} / {} ", c, t),
-                Units::Bytes => format!("{} / {} ", kb_fmt!(c), kb_fmt!(t)),
-            };
+    }
+
+    // drawable tells if the glyphs can be drawn on the output as they are.
+    fn drawable<S: AsRef<str>>(&self, glyphs: &[S]) -> bool {
+        self.caps.unicode || glyphs.iter().all(|g| g.as_ref().is_ascii())
+    }
+
+    // bar_glyphs returns the glyphs of the bar box, the ASCII ones if the
+    // configured ones cannot be drawn on the output.
+    fn bar_glyphs(&self, failed: bool) -> Vec<&str> {
+        let v: Vec<&str> = if failed {
+            self.error_format.iter().map(|s| &s[..]).collect()
+        } else {
+            vec![&self.bar_start,
+                 &self.bar_current,
+                 &self.bar_current_n,
+                 &self.bar_remain,
+                 &self.bar_end]
+        };
+        if self.drawable(&v) {
+            v
+        } else if failed {
+            glyphs(ERROR_FORMAT)
+        } else {
+            glyphs(&self.ascii_format)
         }
-        // tick box
-        if self.show_tick {
-            prefix = prefix + &format!("{} ", self.tick[self.tick_state]);
-        }
-        // bar box
-        if self.show_bar {
-            let p = prefix.len() + suffix.len() + 3;
-            if p < width {
-                let size = width - p;
-                let curr_count = ((self.current as f64 / self.total as f64) * size as f64)
-                    .ceil() as usize;
-                if size >= curr_count {
-                    let rema_count = size - curr_count;
-                    base = self.bar_start.clone();
-                    if rema_count > 0 && curr_count > 0 {
-                        base = base + repeat!(self.bar_current.to_string(), curr_count - 1) +
-                               &self.bar_current_n;
+    }
+
+    // bar_box prepares the bar box.
+    fn bar_box(&self) -> BarBox {
+        let (fill_current, fill_total) = self.fill();
+        let failed = matches!(self.finish_state, Some(FinishState::Error(_)));
+        let colors = self.caps.color;
+        let glyphs = self.bar_glyphs(failed);
+        let segments = if failed || self.fill_counter.is_some() {
+            Vec::new()
+        } else {
+            self.segments
+                .iter()
+                .map(|segment| {
+                    let glyph = if self.drawable(&[&segment.glyph]) {
+                        &segment.glyph[..]
                     } else {
-                        base = base + repeat!(self.bar_current.to_string(), curr_count);
-                    }
-                    base = base + repeat!(self.bar_remain.to_string(), rema_count) + &self.bar_end;
+                        glyphs[1]
+                    };
+                    (glyph.to_owned(), segment.count, segment.color.filter(|_| colors))
+                })
+                .collect()
+        };
+        let buffered_glyph = if self.drawable(&[&self.bar_buffered]) {
+            &self.bar_buffered[..]
+        } else {
+            BUFFERED_GLYPH
+        };
+        BarBox {
+            glyphs: glyphs.iter().map(|g| (*g).to_owned()).collect(),
+            fill_current,
+            fill_total,
+            segments,
+            buffered: if failed || self.fill_counter.is_some() {
+                0.
+            } else {
+                self.buffered as f64
+            },
+            buffered_glyph: buffered_glyph.to_owned(),
+            buffered_color: self.buffered_color.filter(|_| colors),
+            color: self.error_color.filter(|_| failed && colors),
+        }
+    }
+
+    // ellipsis marks where a shortened message was cut.
+    fn ellipsis(&self) -> &'static str {
+        if self.caps.unicode { "…" } else { "..." }
+    }
+
+    // hidden tells whether the bar is not drawn, see `NonTtyBehavior::Hide`.
+    fn hidden(&self) -> bool {
+        self.non_tty == NonTtyBehavior::Hide && !self.caps.terminal
+    }
+
+    // logging tells whether the bar is printed as log lines instead of
+    // being redrawn in place.
+    fn logging(&self) -> bool {
+        !self.caps.redraw
+    }
+
+    // in_place tells whether the bar is drawn on a line of its own, which
+    // can be erased or ended.
+    fn in_place(&self) -> bool {
+        !self.hidden() && !self.logging()
+    }
+
+    // redraw draws the bar, ignoring the max refresh rate.
+    fn redraw(&mut self) {
+        let mrr = self.max_refresh_rate.take();
+        self.draw();
+        self.max_refresh_rate = mrr;
+    }
+
+    fn draw(&mut self) {
+        // a finishing bar keeps the position it finishes at
+        if self.finish_state.is_none() {
+            self.roll_up();
+        }
+        self.sync_parent();
+        let now = SteadyTime::now();
+        let elapsed = self.elapsed(now);
+        self.stats.update(elapsed, self.position());
+        if let Some(mrr) = self.max_refresh_rate {
+            if now - self.last_refresh_time < mrr {
+                return;
+            }
+        }
+        if self.hidden() {
+            return;
+        }
+        // a log gets a line at most every LOG_INTERVAL_SECS
+        if self.logging() {
+            if let Some(last) = self.last_log {
+                if now - last < time::Duration::seconds(LOG_INTERVAL_SECS) {
+                    self.log_pending = true;
+                    return;
                 }
             }
         }
-        out = prefix + &base + &suffix;
+
+        let width = self.width().saturating_sub(self.indent);
+        let state = self.state();
+        let boxes = self.boxes(elapsed, self.speed(elapsed));
+        let builtins: Vec<BuiltinBox> = Builtin::ALL
+            .iter()
+            .map(|&builtin| BuiltinBox { builtin, boxes: &boxes })
+            .collect();
+
+        // render the boxes by priority, each with the width left, so that
+        // the lowest ones are shortened or dropped if the line does not fit.
+        // The bar comes last and takes the rest.
+        let is_bar = |slot: &Slot| matches!(*slot, Slot::Builtin(Builtin::Bar));
+        let reserved = if self.show_bar && self.layout.iter().any(&is_bar) {
+            self.bar_min_width + 3
+        } else {
+            0
+        };
+        let mut parts: Vec<String> = self.layout
+            .iter()
+            .map(|slot| match *slot {
+                Slot::Text(ref text) => text.clone(),
+                _ => String::new(),
+            })
+            .collect();
+        let mut used: usize = parts.iter().map(|part| display_len(part)).sum();
+        let mut order: Vec<(usize, &dyn Widget)> = self.layout
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| self.slot_widget(slot, &builtins).map(|widget| (i, widget)))
+            .collect();
+        order.sort_by_key(|&(i, widget)| {
+            (is_bar(&self.layout[i]), Reverse(widget.priority()), i)
+        });
+        for (i, widget) in order {
+            let left = width.saturating_sub(used);
+            parts[i] = if is_bar(&self.layout[i]) {
+                if self.show_bar && left >= reserved {
+                    widget.render(&state, left)
+                } else {
+                    String::new()
+                }
+            } else {
+                let left = left.saturating_sub(reserved);
+                let part = widget.render(&state, left);
+                if display_len(&part) <= left {
+                    part
+                } else if left > 0 && widget.min_width() <= left {
+                    truncate_display(&part, left)
+                } else {
+                    String::new()
+                }
+            };
+            used += display_len(&parts[i]);
+        }
+        let line = truncate_display(&parts.concat(), width);
         // pad
-        if out.len() < width {
-            let gap = width - out.len();
-            out = out + repeat!(" ", gap);
+        let len = display_len(&line);
+        let pad = if len < width {
+            repeat!(" ", width - len).to_owned()
+        } else {
+            String::new()
+        };
+        let mut out = repeat!(" ", self.indent).to_owned() + &line + &pad;
+        // hide cursor on first draw
+        if self.hide_cursor && !self.is_multibar && !self.cursor_hidden && self.caps.cursor &&
+           self.caps.terminal {
+            out = hide_cursor() + &out;
+            self.cursor_hidden = true;
         }
         // print
-        printfl!(self.handle, "\r{}", out);
+        if self.logging() {
+            printfl!(self.handle, "{}\n", out.trim_end());
+            self.last_log = Some(now);
+            self.log_pending = false;
+        } else {
+            printfl!(self.handle, "\r{}", out);
+        }
 
         self.last_refresh_time = SteadyTime::now();
     }
@@ -408,35 +2243,182 @@ impl<T: Write> ProgressBar<T> {
             }
         }
 
-        if self.current < self.total {
+        if self.position() < self.total_f64() {
             self.current = self.total;
+            self.current_frac = self.total_frac;
             redraw = true;
         }
+        if self.finish_state.is_none() {
+            self.finish_state = Some(FinishState::Success);
+        }
 
-        if redraw {
+        if redraw || self.log_pending {
+            // let the last line through to the log
+            self.last_log = None;
             self.draw();
         }
+        self.restore_cursor();
+        self.end_stage();
+        let elapsed = self.elapsed(SteadyTime::now());
+        self.stats.finish(elapsed, self.position());
+        self.is_finish = true;
+    }
+
+    // finish_draw_as keeps the bar at its current position, and draws it the
+    // last time in the given (non success) state, with `msg` after the
+    // message.
+    fn finish_draw_as(&mut self, state: FinishState, msg: &str) {
+        self.roll_up();
+        let message = self.message.clone() + msg;
+        self.message(&message);
+        self.finish_state = Some(state);
+        self.max_refresh_rate = None;
+        self.last_log = None;
+        self.draw();
+        self.restore_cursor();
+        self.end_stage();
+        let elapsed = self.elapsed(SteadyTime::now());
+        self.stats.finish(elapsed, self.position());
         self.is_finish = true;
     }
 
+    // clear_line erases the bar (or removes it from the MultiBar), and
+    // finishes it in the given state.
+    fn clear_line(&mut self, state: FinishState) {
+        self.sync_parent();
+        self.finish_state = Some(state);
+        self.restore_cursor();
+        self.end_stage();
+        let elapsed = self.elapsed(SteadyTime::now());
+        self.stats.finish(elapsed, self.position());
+        self.is_finish = true;
+        if let Some(ref pipe) = self.multibar {
+            pipe.remove();
+        } else if self.in_place() {
+            let width = self.width();
+            printfl!(self.handle, "\r{}\r", repeat!(" ", width));
+        }
+        self.notify_finish();
+    }
+
+    // notify_finish tells the MultiBar (if any) about the final state.
+    fn notify_finish(&mut self) {
+        if let Some(ref pipe) = self.multibar {
+            let state = self.finish_state.clone().unwrap_or(FinishState::Success);
+            pipe.finish(state, self.stats().elapsed);
+        }
+    }
+
+    // restore_cursor shows the cursor again if it was hidden by `draw`.
+    fn restore_cursor(&mut self) {
+        if self.cursor_hidden {
+            self.cursor_hidden = false;
+            // this also runs on drop, so don't panic on write errors.
+            let _ = self.handle.write_all(show_cursor().as_bytes());
+            let _ = self.handle.flush();
+        }
+    }
+
     /// Calling finish manually will set current to total and draw
     /// the last time
     pub fn finish(&mut self) {
+        if !self.leave_on_finish {
+            return self.finish_and_clear();
+        }
         self.finish_draw();
-        printfl!(self.handle, "");
+        self.notify_finish();
+    }
+
+    /// Set current to total and erase the bar, leaving no trace.
+    ///
+    /// In a MultiBar the line of the bar is removed, and the bars below
+    /// it move up.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.add(10);
+    /// pb.finish_and_clear();
+    /// ```
+    pub fn finish_and_clear(&mut self) {
+        if self.position() < self.total_f64() {
+            self.current = self.total;
+            self.current_frac = self.total_frac;
+        }
+        self.clear_line(FinishState::Success);
+    }
+
+    /// Finish the bar because of an error.
+    ///
+    /// Unlike `finish`, the bar is kept at its current position. It is drawn
+    /// the last time using the error format and color (if the output
+    /// supports colors, see `Capabilities::color`), with `msg` after the
+    /// message.
+    /// Does nothing if the bar already finished.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.add(4);
+    /// pb.finish_with_error("connection reset");
+    /// ```
+    pub fn finish_with_error(&mut self, msg: &str) {
+        if self.is_finish {
+            return;
+        }
+        self.finish_draw_as(FinishState::Error(msg.to_owned()), msg);
+        self.notify_finish();
+    }
+
+    /// Abandon the bar.
+    ///
+    /// The bar is kept at its current position and drawn the last time in
+    /// its regular format, with `msg` after the message.
+    /// Does nothing if the bar already finished.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let mut pb = ProgressBar::new(10);
+    /// pb.add(4);
+    /// pb.abandon("skipped");
+    /// ```
+    pub fn abandon(&mut self, msg: &str) {
+        if self.is_finish {
+            return;
+        }
+        self.finish_draw_as(FinishState::Abandoned(msg.to_owned()), msg);
+        self.notify_finish();
+    }
+
+    /// Get the state the bar finished in, or `None` if it is still running.
+    pub fn finish_state(&self) -> Option<&FinishState> {
+        self.finish_state.as_ref()
     }
 
 
     /// Call finish and write string `s` that will replace the progress bar.
     pub fn finish_print(&mut self, s: &str) {
         self.finish_draw();
+        if !self.in_place() {
+            printfl!(self.handle, "{}\n", s);
+            return self.notify_finish();
+        }
         let width = self.width();
-        let mut out = format!("{}", s);
+        let mut out = s.to_owned();
         if s.len() < width {
             out += repeat!(" ", width - s.len());
         };
         printfl!(self.handle, "\r{}", out);
-        self.finish();
+        self.notify_finish();
     }
 
 
@@ -451,7 +2433,20 @@ impl<T: Write> ProgressBar<T> {
             return self.finish_print(s);
         }
         self.finish_draw();
-        printfl!(self.handle, "\n{}", s);
+        if !self.in_place() {
+            printfl!(self.handle, "{}\n", s);
+        } else {
+            printfl!(self.handle, "\n{}", s);
+        }
+    }
+
+    /// Call finish and write the `stats_summary()` line below the progress bar.
+    ///
+    /// In a MultiBar the summary replaces the progress bar, see `finish_println`.
+    pub fn finish_println_stats(&mut self) {
+        self.finish_draw();
+        let summary = self.stats_summary();
+        self.finish_println(&summary);
     }
 
     /// Get terminal width, from configuration, terminal size, or default(80)
@@ -466,6 +2461,59 @@ impl<T: Write> ProgressBar<T> {
     }
 }
 
+impl<T: Write + Send + 'static> ProgressBar<T> {
+    /// Share the bar, so its children (see `add_child`) redraw it whenever
+    /// they change.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::ProgressBar;
+    ///
+    /// let pb = ProgressBar::new(100).into_shared();
+    /// let mut scan = pb.lock().unwrap().create_child(5., 20);
+    /// for _ in 0..20 {
+    ///     // also redraws pb
+    ///     scan.inc();
+    /// }
+    /// ```
+    pub fn into_shared(self) -> Arc<Mutex<ProgressBar<T>>> {
+        let pb = Arc::new(Mutex::new(self));
+        let redraw: Arc<Mutex<dyn Redraw + Send>> = pb.clone();
+        pb.lock().unwrap().shared = Some(Arc::downgrade(&redraw));
+        pb
+    }
+}
+
+impl<T: Write> Drop for ProgressBar<T> {
+    fn drop(&mut self) {
+        // don't draw while unwinding, a failing write would abort.
+        if !self.is_finish && !::std::thread::panicking() {
+            match self.drop_behavior {
+                DropBehavior::Finish => self.finish(),
+                DropBehavior::Abandon => self.abandon(""),
+                DropBehavior::Clear => self.clear_line(FinishState::Abandoned(String::new())),
+                DropBehavior::Leave => {}
+            }
+            let end_line = match self.drop_behavior {
+                DropBehavior::Finish => self.leave_on_finish,
+                DropBehavior::Abandon => true,
+                _ => false,
+            };
+            if end_line && !self.is_multibar && self.in_place() {
+                printfl!(self.handle, "\n");
+            }
+        }
+        self.restore_cursor();
+        // the MultiBar must not wait for a bar that is gone.
+        if !self.is_finish {
+            self.finish_state = Some(FinishState::Abandoned(self.message.clone()));
+            self.is_finish = true;
+            self.notify_finish();
+        }
+    }
+}
+
 // Implement io::Writer
 impl<T: Write> Write for ProgressBar<T> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
@@ -478,21 +2526,149 @@ impl<T: Write> Write for ProgressBar<T> {
     }
 }
 
+// fmt_number formats a number in the given units, with `precision` decimals
+// for the default units
+fn fmt_number(units: &Units, n: f64, precision: usize, nf: &NumberFormat) -> String {
+    match *units {
+        Units::Default => nf.format(n, precision),
+        Units::Bytes => kb_fmt!(n).replace('.', &nf.decimal.to_string()),
+    }
+}
+
+// fmt_speed formats the speed box
+fn fmt_speed(units: &Units, speed: f64, nf: &NumberFormat) -> String {
+    fmt_number(units, speed, 2, nf) + "/s"
+}
+
+// glyphs splits a format into its glyphs
+fn glyphs(fmt: &str) -> Vec<&str> {
+    fmt.split("").filter(|x| !x.is_empty()).collect()
+}
+
+// fmt_time_left formats the time left box
+fn fmt_time_left(left: f64) -> String {
+    if left < 60. {
+        format!("{:.0}s", left)
+    } else {
+        format!("{:.0}m", left / 60.)
+    }
+}
+
+// display_len is the number of characters `s` takes on the terminal,
+// not counting color escape codes.
+fn display_len(s: &str) -> usize {
+    let mut len = 0;
+    let mut escape = false;
+    for c in s.chars() {
+        match c {
+            '\x1B' => escape = true,
+            'm' if escape => escape = false,
+            _ if escape => {}
+            _ => len += 1,
+        }
+    }
+    len
+}
+
+// truncate_message shortens `s` to at most `n` characters, marking the cut
+// with `ellipsis`.
+fn truncate_message(s: &str, n: usize, truncation: Truncation, ellipsis: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let len = chars.len();
+    if len <= n {
+        return s.to_owned();
+    } else if n == 0 {
+        return String::new();
+    }
+    let ellipsis = if ellipsis.chars().count() <= n { ellipsis } else { "" };
+    let keep = n - ellipsis.chars().count();
+    match truncation {
+        Truncation::End => chars[..keep].iter().collect::<String>() + ellipsis,
+        Truncation::Start => ellipsis.to_owned() + &chars[len - keep..].iter().collect::<String>(),
+        Truncation::Middle => {
+            let head = keep - keep / 2;
+            chars[..head].iter().collect::<String>() + ellipsis +
+            &chars[len - (keep - head)..].iter().collect::<String>()
+        }
+        Truncation::Path => {
+            let mut dirs: Vec<String> = s.split('/').map(|d| d.to_owned()).collect();
+            let last = dirs.len() - 1;
+            for i in 0..last {
+                if dirs.join("/").chars().count() <= n {
+                    break;
+                }
+                // keep the dot of hidden directories
+                let short = if dirs[i].starts_with('.') { 2 } else { 1 };
+                dirs[i] = dirs[i].chars().take(short).collect();
+            }
+            truncate_message(&dirs.join("/"), n, Truncation::Start, ellipsis)
+        }
+    }
+}
+
+// truncate_display cuts `s` to at most `n` characters on the terminal,
+// keeping color escape codes.
+fn truncate_display(s: &str, n: usize) -> String {
+    if display_len(s) <= n {
+        return s.to_owned();
+    }
+    let mut out = String::new();
+    let (mut len, mut escape, mut colored) = (0, String::new(), false);
+    for c in s.chars() {
+        if c == '\x1B' || !escape.is_empty() {
+            // keep the escapes, a color is open until it is reset
+            escape.push(c);
+            if c == 'm' {
+                colored = escape != "\x1B[0m" && escape != "\x1B[m";
+                out += &escape;
+                escape.clear();
+            }
+            continue;
+        }
+        if len < n {
+            out.push(c);
+            len += 1;
+        }
+    }
+    if colored {
+        out += "\x1B[0m";
+    }
+    out
+}
+
 fn time_to_std(d: time::Duration) -> Duration {
-    assert!(d > time::Duration::zero());
+    assert!(d >= time::Duration::zero());
 
     let secs = d.num_seconds();
     let nsecs = (d - time::Duration::seconds(secs)).num_nanoseconds().unwrap();
     Duration::new(secs as u64, nsecs as u32)
 }
 
-fn fract_dur(d: Duration) -> f64 {
+pub(crate) fn fract_dur(d: Duration) -> f64 {
     d.as_secs() as f64 + d.subsec_nanos() as f64 / NANOS_PER_SEC as f64
 }
 
 #[cfg(test)]
 mod test {
-    use pb::ProgressBar;
+    use std::iter::repeat;
+    use std::thread;
+    use std::time::Duration;
+    use time::{self, SteadyTime};
+    use pb::{ProgressBar, ProgressState, Units, FinishState, DropBehavior, OverflowBehavior,
+             Truncation, NonTtyBehavior, FORMAT, ERROR_FORMAT};
+    use number::NumberFormat;
+    use style::ProgressStyle;
+    use env::Capabilities;
+    use tty::{show_cursor, hide_cursor};
+    use std::io::Write;
+
+    // plain creates a bar that draws as on an output that is not a
+    // terminal, whatever the tests run in.
+    fn plain<T: Write>(handle: T, total: u64) -> ProgressBar<T> {
+        let mut pb = ProgressBar::on(handle, total);
+        pb.set_capabilities(Capabilities::default());
+        pb
+    }
 
     #[test]
     fn add() {
@@ -515,8 +2691,11 @@ mod test {
         let fmt = "[~> ]";
         let mut pb = ProgressBar::new(1);
         pb.format(fmt);
-        assert!(pb.bar_start + &pb.bar_current + &pb.bar_current_n + &pb.bar_remain +
+        assert!(pb.bar_start.clone() + &pb.bar_current + &pb.bar_current_n + &pb.bar_remain +
                 &pb.bar_end == fmt);
+        pb.format("[=>-]~");
+        assert_eq!(pb.bar_end, "]", "should ignore extra glyphs");
+        assert_eq!(pb.bar_buffered, "~");
     }
 
     #[test]
@@ -527,6 +2706,674 @@ mod test {
         assert!(pb.is_finish, "should set is_finish to true");
     }
 
+    #[test]
+    fn finish_with_error() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.message("copy: ");
+        pb.add(4);
+        pb.finish_with_error("boom");
+        assert!(pb.current == 4, "should keep current at its position");
+        assert!(pb.is_finish, "should set is_finish to true");
+        assert_eq!(pb.finish_state(), Some(&FinishState::Error("boom".to_owned())));
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        let last = out.rsplit('\r').next().unwrap();
+        assert!(last.starts_with("copy: boom 4 / 10 [="),
+                "should draw `msg` after the message");
+        assert!(last.contains("=X-"), "should draw the bar with the error format");
+        assert!(!out.contains('\x1B'), "should not color a plain output");
+
+        let mut pb = plain(Vec::new(), 10);
+        pb.error_format("╢X░");
+        assert_eq!(pb.error_format.concat(), ERROR_FORMAT, "should ignore a short format");
+        pb.set_capabilities(Capabilities { color: true, ..Capabilities::default() });
+        pb.finish_with_error("boom");
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        assert!(out.contains("\x1B[31m["), "should color the bar in red");
+    }
+
+    #[test]
+    fn abandon() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.add(4);
+        pb.abandon("skipped");
+        assert!(pb.current == 4, "should keep current at its position");
+        assert_eq!(pb.finish_state(), Some(&FinishState::Abandoned("skipped".to_owned())));
+    }
+
+    #[test]
+    fn drop_abandons() {
+        let mut out = Vec::new();
+        {
+            let mut pb = plain(&mut out, 10);
+            pb.add(3);
+        }
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with('\n'), "should end the line");
+        assert!(out.rsplit('\r').next().unwrap().starts_with(" 3 / 10 "),
+                "should keep the bar at its position");
+
+        let mut out = Vec::new();
+        {
+            let mut pb = plain(&mut out, 10);
+            pb.message("copy: ");
+            pb.add(3);
+        }
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.rsplit('\r').next().unwrap().starts_with("copy:  3 / 10 "),
+                "should draw the message once");
+    }
+
+    #[test]
+    fn drop_clear() {
+        let mut out = Vec::new();
+        {
+            let mut pb = plain(&mut out, 10);
+            pb.set_width(Some(20));
+            pb.set_drop_behavior(DropBehavior::Clear);
+            pb.add(3);
+        }
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with(&format!("\r{}\r", repeat!(" ", 20))),
+                "should erase the line");
+    }
+
+    #[test]
+    fn finish_and_clear() {
+        let mut out = Vec::new();
+        {
+            let mut pb = plain(&mut out, 10);
+            pb.set_width(Some(20));
+            pb.leave_on_finish(false);
+            pb.add(3);
+            pb.finish();
+            assert!(pb.current == pb.total, "should set current to total");
+            assert_eq!(pb.finish_state(), Some(&FinishState::Success));
+        }
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with(&format!("\r{}\r", repeat!(" ", 20))),
+                "should erase the line");
+    }
+
+    #[test]
+    fn pause() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.pause();
+        assert!(pb.is_paused());
+        let tick_state = pb.tick_state;
+        pb.tick();
+        assert!(pb.tick_state == tick_state, "should not move the tick while paused");
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        assert!(out.trim_end().ends_with("paused"), "should show the paused indicator");
+        thread::sleep(Duration::from_millis(50));
+        let elapsed = pb.elapsed(SteadyTime::now());
+        assert!(elapsed < time::Duration::milliseconds(50),
+                "should not count the paused time");
+        pb.resume();
+        assert!(!pb.is_paused());
+        assert!(pb.paused_time >= time::Duration::milliseconds(50));
+
+        for style in &[ProgressStyle::cargo(), ProgressStyle::npm()] {
+            let mut pb = plain(Vec::new(), 10);
+            pb.set_style(style);
+            pb.pause();
+            pb.tick();
+            let out = String::from_utf8(pb.handle.clone()).unwrap();
+            assert!(out.trim_end().ends_with("paused"), "should show the paused indicator");
+        }
+    }
+
+    #[test]
+    fn set_total() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.add(5);
+        pb.inc_total(10);
+        assert!(pb.total == 20, "should add to total");
+        pb.set_total(2);
+        assert!(pb.current == 5, "should keep current");
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        assert!(out.rsplit('\r').next().unwrap().starts_with("5 / 2 (+3 over) "),
+                "should show the overflow");
+    }
+
+    #[test]
+    fn overflow() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.set_overflow(OverflowBehavior::Clamp);
+        pb.add(15);
+        assert!(pb.current == 10, "should clamp current to total");
+        pb.set_overflow(OverflowBehavior::Grow);
+        pb.add(5);
+        assert!(pb.total == 15, "should grow total to current");
+    }
+
+    #[test]
+    fn empty_total() {
+        let mut pb = plain(Vec::new(), 0);
+        pb.set_width(Some(30));
+        pb.set_buffered(0);
+        pb.tick();
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        assert!(out.rsplit('\r').next().unwrap().contains("[---"),
+                "should draw an empty bar");
+        assert!(!out.contains('='), "should not fill the bar");
+    }
+
+    #[test]
+    fn sub() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.add(5);
+        assert!(pb.sub(2) == 3, "should subtract the given `n` from current");
+        assert!(pb.dec() == 2, "should decrement current by 1");
+        assert!(pb.sub(5) == 0, "should not go below zero");
+    }
+
+    #[test]
+    fn rewind_to() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.add(8);
+        pb.rewind_to(5);
+        assert!(pb.current == 5, "should set current to the given position");
+        assert!(pb.rate_current == 5., "should restart the rate estimation");
+
+        let mut pb = plain(Vec::new(), 10);
+        pb.add(3);
+        pb.finish();
+        pb.rewind_to(5);
+        assert_eq!(pb.state().rate, 0., "should not measure a rewound finished bar");
+    }
+
+    #[test]
+    fn reset() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.add(10);
+        pb.finish();
+        pb.reset();
+        assert!(pb.current == 0, "should reset current");
+        assert!(!pb.is_finish, "should reset is_finish");
+        assert_eq!(pb.finish_state(), None);
+    }
+
+    #[test]
+    fn fractional() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.set_precision(2);
+        assert!(pb.total == 10, "should keep total in whole units");
+        pb.set_total_f64(12.5);
+        assert!(pb.total == 12);
+        assert!(pb.set_f64(2.25) == 2.25);
+        assert!(pb.add_f64(0.5) == 2.75);
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        assert!(out.rsplit('\r').next().unwrap().starts_with(" 2.75 / 12.50 "),
+                "should show values with the given precision");
+        assert!(pb.inc() == 3, "should add whole units");
+        assert!(pb.state().position == 3.75);
+        pb.set_fraction(0.5);
+        assert!(pb.current == 6);
+        pb.set_precision(100);
+        pb.tick();
+    }
+
+    #[test]
+    fn counters() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.set_width(Some(80));
+        pb.add_counter("copied", 4096, Units::Bytes);
+        assert_eq!(pb.counter_add("copied", 1024), Some(1024));
+        assert_eq!(pb.counter_add("missing", 1), None);
+        pb.inc();
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        assert!(out.rsplit('\r').next().unwrap().starts_with(" 1 / 10 1.00 KB / 4.00 KB copied "),
+                "should draw secondary counters after the main one");
+        pb.fill_with_counter(Some("copied"));
+        assert_eq!(pb.fill(), (1024., 4096.));
+    }
+
+    #[test]
+    fn segments() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.set_width(Some(51));
+        pb.show_speed = false;
+        pb.show_percent = false;
+        pb.show_time_left = false;
+        pb.inc_ok();
+        pb.inc_ok();
+        pb.inc_err();
+        pb.inc_skip();
+        assert!(pb.current == 4, "should add to current");
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        let last = out.rsplit('\r').next().unwrap();
+        assert_eq!(last, " 4 / 10 (2 ok, 1 err, 1 skip) [====xx~~----------] ",
+                "should draw the tallies and stacked segments");
+    }
+
+    #[test]
+    fn display_len() {
+        assert_eq!(super::display_len("ab"), 2);
+        assert_eq!(super::display_len("╢▌░╟"), 4);
+        assert_eq!(super::display_len("\x1B[31mab\x1B[0m"), 2);
+    }
+
+    #[test]
+    fn buffered() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.set_width(Some(25));
+        pb.show_speed = false;
+        pb.show_percent = false;
+        pb.show_time_left = false;
+        pb.show_counter = false;
+        pb.format("[=>-]");
+        pb.set_buffered_glyph("+");
+        pb.set_buffered_glyph("++");
+        assert_eq!(pb.bar_buffered, "+", "should ignore more than one glyph");
+        pb.set_buffered(6);
+        pb.add(2);
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        assert_eq!(out.rsplit('\r').next().unwrap(), "[====>+++++++++--------] ");
+    }
+
+    #[test]
+    fn children() {
+        let mut pb = plain(Vec::new(), 100);
+        let mut scan = pb.create_child(1., 10);
+        let mut download = pb.create_child(3., 10);
+        scan.finish();
+        download.add(5);
+        pb.tick();
+        assert!(pb.current == 63, "should roll up the weighted fractions");
+        download.finish();
+        pb.tick();
+        assert!(pb.current == 100);
+    }
+
+    #[test]
+    fn finish_parent() {
+        let mut pb = plain(Vec::new(), 100);
+        let mut scan = pb.create_child(1., 10);
+        scan.add(5);
+        pb.finish();
+        pb.tick();
+        assert!(pb.current == 100, "should not roll up after the bar finished");
+        pb.reset();
+        pb.abandon("skipped");
+        assert!(pb.current == 50, "should keep the children's progress");
+    }
+
+    #[test]
+    fn shared_parent() {
+        let pb = plain(Vec::new(), 100).into_shared();
+        let mut scan = pb.lock().unwrap().create_child(1., 10);
+        scan.add(5);
+        let pb = pb.lock().unwrap();
+        assert!(pb.current == 50, "should redraw the parent");
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        assert!(out.rsplit('\r').next().unwrap().starts_with(" 50 / 100 "));
+    }
+
+    #[test]
+    fn stages() {
+        let mut pb = plain(Vec::new(), 0);
+        pb.set_stages(&["Fetching", "Compiling"]);
+        assert!(pb.next_stage(3));
+        pb.inc();
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        assert!(out.rsplit('\r').next().unwrap().starts_with("[1/2] Fetching 1 / 3 "),
+                "should prefix the bar with the stage");
+        assert!(pb.next_stage(5));
+        assert!(pb.current == 0 && pb.total == 5, "should reset the bar");
+        pb.finish();
+        assert!(!pb.next_stage(1), "should have no more stages");
+        let times = pb.stage_times();
+        assert_eq!(times.len(), 2);
+        assert_eq!(times[1].0, "Compiling");
+        assert!(pb.stage_report().starts_with("[1/2] Fetching 0.0"));
+    }
+
+    #[test]
+    fn stats() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.add(5);
+        assert!(pb.stats_summary().starts_with("running for "));
+        // let 1.1s pass without progress
+        pb.start_time = pb.start_time - time::Duration::milliseconds(1100);
+        pb.add(5);
+        pb.finish();
+        let stats = pb.stats();
+        assert!(stats.elapsed >= Duration::from_millis(1100));
+        assert!(stats.stalled >= Duration::from_millis(1100), "should count the stall");
+        assert!(stats.average_speed > 0. && stats.average_speed < 10.);
+        assert!(stats.peak_speed >= stats.average_speed);
+        pb.start_time = pb.start_time - time::Duration::milliseconds(100);
+        assert_eq!(pb.stats().elapsed, stats.elapsed, "should freeze on finish");
+        assert!(pb.stats_summary().starts_with("done in 1."));
+
+        let mut pb = plain(Vec::new(), 10);
+        pb.add(5);
+        pb.finish_with_error("boom");
+        assert!(pb.stats_summary().starts_with("failed after "));
+    }
+
+    #[test]
+    fn state() {
+        let mut pb = plain(Vec::new(), 4);
+        pb.set_precision(1);
+        pb.message("copy ");
+        pb.add_f64(1.5);
+        thread::sleep(Duration::from_millis(100));
+        let state = pb.state();
+        assert_eq!(state.position, 1.5);
+        assert_eq!(state.total, 4.);
+        assert_eq!(state.fraction, 0.375);
+        assert_eq!(state.message, "copy ");
+        assert!(state.rate > 0.);
+        assert!(state.eta.is_some());
+        assert_eq!(state.finish_state, None);
+        pb.pause();
+        assert!(pb.state().paused);
+        assert_eq!(pb.state().eta, None);
+        pb.finish_with_error("failed");
+        assert_eq!(pb.state().finish_state, Some(FinishState::Error("failed".to_owned())));
+    }
+
+    #[test]
+    fn layout() {
+        let mut out = Vec::new();
+        {
+            let mut pb = plain(&mut out, 10);
+            pb.set_width(Some(40));
+            pb.set_bar_min_width(4);
+            pb.set_layout("{counter}{bar}{percent}| {left}!");
+            pb.add_widget("left", |state: &ProgressState, _: usize| {
+                format!("{} left", state.total - state.position)
+            });
+            pb.add_prefix_widget(|_: &ProgressState, _: usize| "> ".to_owned());
+            pb.add_suffix_widget(|_: &ProgressState, width: usize| format!(" {}", width));
+            pb.set(4);
+            pb.set_drop_behavior(DropBehavior::Leave);
+        }
+        let out = String::from_utf8(out).unwrap();
+        let last = out.rsplit('\r').next().unwrap();
+        // widgets get the width left by the higher priority boxes and the bar
+        assert_eq!(last, " 4 / 10 > [=>---]  40.00 % | 6 left! 12 ");
+    }
+
+    #[test]
+    fn replace_builtin() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.set_width(Some(20));
+        pb.set_layout("{bar}{percent}");
+        pb.add_widget("percent", |state: &ProgressState, _: usize| {
+            format!(" {:.0}%", state.fraction * 100.)
+        });
+        pb.set(5);
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        let last = out.rsplit('\r').next().unwrap();
+        assert_eq!(last, "[======>------] 50% ");
+    }
+
+    #[test]
+    fn narrow() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.message("copying files ");
+        pb.show_time_left = false;
+        pb.set_width(Some(40));
+        pb.add(4);
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        let last = out.rsplit('\r').next().unwrap();
+        assert_eq!(last, "copying files [=====>-------]  40.00 %  ",
+                   "should drop the speed and counter first");
+
+        pb.set_width(Some(28));
+        pb.set_bar_min_width(5);
+        pb.add(1);
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        let last = out.rsplit('\r').next().unwrap();
+        assert_eq!(last, "copying f…[==>--]  50.00 %  ", "should shorten the message");
+        assert_eq!(super::truncate_display("\x1B[31mabc\x1B[0m", 2),
+                   "\x1B[31mab\x1B[0m");
+        assert_eq!(super::truncate_display("\x1B[31mabc", 2), "\x1B[31mab\x1B[0m");
+    }
+
+    #[test]
+    fn truncate_message() {
+        use super::truncate_message;
+
+        let s = "copying files";
+        assert_eq!(truncate_message(s, 20, Truncation::End, "…"), s);
+        assert_eq!(truncate_message(s, 10, Truncation::End, "…"), "copying f…");
+        assert_eq!(truncate_message(s, 10, Truncation::Start, "…"), "…ing files");
+        assert_eq!(truncate_message(s, 10, Truncation::Middle, "…"), "copyi…iles");
+        assert_eq!(truncate_message(s, 10, Truncation::End, "..."), "copying...");
+        let path = "/home/user/projects/very_long_name.rs";
+        assert_eq!(truncate_message(path, 31, Truncation::Path, "…"),
+                   "/h/u/projects/very_long_name.rs");
+        assert_eq!(truncate_message(path, 24, Truncation::Path, "…"), "/h/u/p/very_long_name.rs");
+        assert_eq!(truncate_message("/home/.config/pbr.toml", 17, Truncation::Path, "…"),
+                   "/h/.c/pbr.toml");
+        assert_eq!(truncate_message(path, 10, Truncation::Path, "…"), "…g_name.rs");
+    }
+
+    #[test]
+    fn message_width() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.set_width(Some(40));
+        pb.show_speed = false;
+        pb.show_time_left = false;
+        pb.show_percent = false;
+        pb.set_message_width(Some(8));
+        pb.message("copy");
+        pb.add(4);
+        pb.set_truncation(Truncation::Start);
+        pb.message("copying files");
+        pb.add(1);
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        let lines: Vec<&str> = out.split('\r').skip(1).collect();
+        assert!(lines[0].starts_with("copy     4 / 10 ["));
+        assert!(lines[1].starts_with("…g files 5 / 10 ["));
+        assert_eq!(lines[0].len(), lines[1].len() - "…".len() + 1,
+                   "should not move the bar");
+    }
+
+    #[test]
+    fn fixed_box_width() {
+        let mut pb = plain(Vec::new(), 100);
+        pb.set_width(Some(60));
+        pb.show_time_left = false;
+        pb.add(9);
+        pb.add(1);
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        let lines: Vec<&str> = out.split('\r').skip(1).collect();
+        assert!(lines[0].starts_with("  9 / 100 ["));
+        assert!(lines[1].starts_with(" 10 / 100 ["));
+        assert!(lines[1].contains("]  10.00 % "));
+        assert_eq!(lines[0].find(']'), lines[1].find(']'), "should not move the bar");
+
+        pb.set_fixed_box_width(false);
+        pb.show_speed = false;
+        pb.add(1);
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        assert!(out.rsplit('\r').next().unwrap().starts_with("11 / 100 ["));
+    }
+
+    #[test]
+    fn number_format() {
+        let mut pb = plain(Vec::new(), 10_000_000);
+        pb.set_width(Some(80));
+        pb.show_speed = false;
+        pb.show_time_left = false;
+        pb.set_number_format(NumberFormat::locale("fr_FR"));
+        pb.set_percent_precision(0);
+        pb.set(1_234_567);
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        let last = out.rsplit('\r').next().unwrap();
+        assert!(last.starts_with(" 1 234 567 / 10 000 000 ["));
+        assert!(last.contains("]  12 % "));
+
+        let nf = NumberFormat { compact: true, ..NumberFormat::default() };
+        assert_eq!(nf.format(999., 0), "999");
+        assert_eq!(nf.format(1500., 0), "1.5k");
+        assert_eq!(nf.format(-2_500_000., 0), "-2.5M");
+        assert_eq!(nf.format(999_950., 0), "1.0M", "should round before picking the suffix");
+        assert_eq!(nf.format(999_949., 0), "999.9k");
+        assert_eq!(nf.format(999.96, 0), "1.0k");
+        assert_eq!(nf.format(999.6, 0), "1.0k");
+        assert_eq!(nf.format(1e20, 0), "100000.0P");
+        assert_eq!(nf.format(999.96, 2), "999.96");
+        assert_eq!(NumberFormat::locale("en").format(-1234.5, 1), "-1,234.5");
+        assert_eq!(NumberFormat::default().format(1234.5, 1), "1234.5");
+    }
+
+    #[test]
+    fn style() {
+        let mut pb = plain(Vec::new(), 120);
+        pb.set_width(Some(40));
+        pb.set_style(&ProgressStyle::cargo());
+        pb.message("Building ");
+        pb.set(60);
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        assert_eq!(out.rsplit('\r').next().unwrap(),
+                   "Building [========>        ]  60 / 120  ");
+
+        pb.set_style(&ProgressStyle { buffered_glyph: "+".to_owned(), ..ProgressStyle::default() });
+        assert!(!pb.show_tick);
+        assert!(pb.show_speed);
+        assert_eq!(pb.bar_remain, "-");
+        assert_eq!(pb.bar_buffered, "+");
+
+        pb.set_max_refresh_rate(Some(Duration::from_millis(100)));
+        pb.set_error_color(None);
+        pb.set_non_tty(NonTtyBehavior::Hide);
+        pb.set_style(&ProgressStyle::wget());
+        assert!(pb.max_refresh_rate.is_some(), "should keep what the style doesn't set");
+        assert_eq!(pb.error_color, None);
+        assert_eq!(pb.non_tty, NonTtyBehavior::Hide);
+    }
+
+    #[test]
+    fn non_tty_hide() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.set_non_tty(NonTtyBehavior::Hide);
+        pb.add(5);
+        pb.finish_println("done");
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        assert_eq!(out, "done\n", "should only print the final line");
+    }
+
+    #[test]
+    fn capabilities() {
+        let detect = |tty, vars: &[(&str, &str)]| {
+            Capabilities::from_vars(tty, |name| {
+                vars.iter().find(|v| v.0 == name).map(|v| v.1.to_owned())
+            })
+        };
+        let caps = detect(true, &[("TERM", "xterm"), ("LANG", "en_US.UTF-8")]);
+        assert_eq!(caps,
+                   Capabilities {
+                       color: true,
+                       cursor: true,
+                       unicode: true,
+                       redraw: true,
+                       terminal: true,
+                   });
+        assert!(!detect(false, &[]).terminal);
+        assert!(!detect(true, &[("NO_COLOR", "1")]).color);
+        assert!(!detect(false, &[]).color);
+        assert!(detect(false, &[("CLICOLOR_FORCE", "1")]).color);
+        assert!(!detect(false, &[("CLICOLOR_FORCE", "0")]).color);
+        assert!(!detect(true, &[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")]).color);
+        let dumb = detect(true, &[("TERM", "dumb")]);
+        assert!(!dumb.color && !dumb.cursor && !dumb.redraw);
+        assert!(!detect(true, &[("CI", "true")]).redraw);
+        assert!(!detect(true, &[("LANG", "C")]).unicode);
+        assert!(!detect(true, &[("LC_ALL", "POSIX"), ("LANG", "en_US.UTF-8")]).unicode);
+        assert!(detect(true, &[("LC_CTYPE", "de_DE.utf8")]).unicode);
+    }
+
+    #[test]
+    fn ascii() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.set_width(Some(40));
+        pb.show_speed = false;
+        pb.show_time_left = false;
+        pb.show_counter = false;
+        pb.format("╢▌▌░╟");
+        pb.tick_format("⠋⠙⠹");
+        pb.show_tick = false;
+        pb.set_ascii(true);
+        assert_eq!(pb.bar_glyphs(false).concat(),
+                   FORMAT,
+                   "should fall back to the ASCII glyphs");
+        assert_eq!(pb.bar_glyphs(true).concat(), ERROR_FORMAT);
+        pb.ascii_format("|#>.|");
+        pb.message("copying files and more ");
+        pb.set_message_width(Some(10));
+        pb.add(5);
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        let last = out.rsplit('\r').next().unwrap();
+        assert!(last.is_ascii(), "should only draw ASCII: {:?}", last);
+        assert!(last.starts_with("copying...|########>...."), "{:?}", last);
+        pb.set_ascii(false);
+        assert_eq!(pb.bar_glyphs(false).concat(),
+                   "╢▌▌░╟",
+                   "should switch back to Unicode");
+        pb.set_buffered_glyph("▒");
+        pb.set_ascii(true);
+        pb.set_ascii(false);
+        assert_eq!(pb.bar_buffered, "▒", "should keep the configured glyphs");
+
+        let mut pb = plain(Vec::new(), 10);
+        pb.set_width(Some(40));
+        pb.tick_format("⠋⠙⠹");
+        pb.set_buffered_glyph("▒");
+        pb.add_segment("ok", "█", None);
+        pb.set_ascii(true);
+        pb.set_buffered(8);
+        pb.segment_add("ok", 5);
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        let last = out.rsplit('\r').next().unwrap();
+        assert!(last.is_ascii(), "should draw ASCII ticks and segments: {:?}", last);
+        assert!(last.contains('~'), "should draw the ASCII buffered glyph: {:?}", last);
+    }
+
+    #[test]
+    fn log_lines() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.set_width(Some(40));
+        pb.set_capabilities(Capabilities { redraw: false, ..Capabilities::default() });
+        pb.add(1);
+        pb.add(1);
+        pb.finish_println("done");
+        let out = String::from_utf8(pb.handle.clone()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(!out.contains('\r'), "should not redraw");
+        assert_eq!(lines.len(), 3, "should skip lines within the interval");
+        assert!(lines[0].starts_with(" 1 / 10"));
+        assert!(lines[1].starts_with("10 / 10"), "should print the last line");
+        assert_eq!(lines[2], "done");
+    }
+
+    #[test]
+    fn finish_restores_cursor() {
+        let mut pb = plain(Vec::new(), 10);
+        pb.cursor_hidden = true;
+        pb.finish();
+        assert!(!pb.cursor_hidden, "should show the cursor again");
+        assert!(pb.handle.ends_with(show_cursor().as_bytes()));
+    }
+
+    #[test]
+    fn hide_cursor_on_terminal() {
+        let mut pb = ProgressBar::on(Vec::new(), 10);
+        pb.inc();
+        let hide = hide_cursor();
+        assert!(!pb.handle.windows(hide.len()).any(|w| w == hide.as_bytes()),
+                "should not assume the handle is a terminal");
+        let mut pb = plain(Vec::new(), 10);
+        pb.set_capabilities(Capabilities { terminal: true, ..Capabilities::default() });
+        pb.inc();
+        assert!(pb.handle.windows(hide.len()).any(|w| w == hide.as_bytes()),
+                "should hide the cursor on a terminal");
+    }
+
     #[test]
     fn kb_fmt() {
         let kb = 1024f64;
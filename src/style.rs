@@ -0,0 +1,341 @@
+use std::time::Duration;
+use pb::{Units, Color, NonTtyBehavior, FORMAT, TICK_FORMAT, BUFFERED_GLYPH};
+use widget::{DEFAULT_LAYOUT, check_layout};
+use builder::BuildError;
+#[cfg(feature = "config")]
+use std::{env, fmt, fs, io};
+#[cfg(feature = "config")]
+use std::error::Error;
+#[cfg(feature = "config")]
+use std::path::Path;
+
+/// A reusable set of bar glyphs, spinner frames, visible boxes, units,
+/// layout, colors, refresh rate and non-TTY behavior, applied with
+/// `ProgressBar::set_style` or `MultiBar::set_style`.
+///
+/// The colors, refresh rate and non-TTY behavior are optional: a style
+/// that leaves them `None` keeps what the bar has.
+///
+/// With the `config` feature, styles can be loaded from TOML, see
+/// `ProgressStyle::from_env`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use pbr::{ProgressBar, ProgressStyle};
+///
+/// let style = ProgressStyle {
+///     format: "|#--|".to_owned(),
+///     show_speed: false,
+///     ..ProgressStyle::default()
+/// };
+/// let mut pb1 = ProgressBar::new(10);
+/// pb1.set_style(&style);
+/// let mut pb2 = ProgressBar::new(20);
+/// pb2.set_style(&ProgressStyle::cargo());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize),
+           serde(default, deny_unknown_fields))]
+pub struct ProgressStyle {
+    /// The bar glyphs, see `ProgressBar::format`.
+    pub format: String,
+    /// The bar glyphs without Unicode, see `ProgressBar::ascii_format`.
+    pub ascii_format: String,
+    /// The spinner frames, see `ProgressBar::tick_format`.
+    pub tick_format: String,
+    /// The spinner frames without Unicode, see
+    /// `ProgressBar::ascii_tick_format`.
+    pub ascii_tick_format: String,
+    /// The glyph of the buffered part, see
+    /// `ProgressBar::set_buffered_glyph`.
+    pub buffered_glyph: String,
+    /// The order of the boxes, see `ProgressBar::set_layout`.
+    pub layout: String,
+    /// The units of the counter and speed boxes.
+    pub units: Units,
+    /// Show the bar box.
+    pub show_bar: bool,
+    /// Show the speed box.
+    pub show_speed: bool,
+    /// Show the percent box.
+    pub show_percent: bool,
+    /// Show the counter box.
+    pub show_counter: bool,
+    /// Show the time left box.
+    pub show_time_left: bool,
+    /// Show the spinner.
+    pub show_tick: bool,
+    /// Show the message box.
+    pub show_message: bool,
+    /// The color of a failed bar, see `ProgressBar::set_error_color`.
+    pub error_color: Option<Color>,
+    /// The color of the buffered part, see `ProgressBar::set_buffered_color`.
+    pub buffered_color: Option<Color>,
+    /// See `ProgressBar::set_max_refresh_rate`, in milliseconds in a config.
+    #[cfg_attr(feature = "serde", serde(rename = "max_refresh_rate_ms", with = "millis"))]
+    pub max_refresh_rate: Option<Duration>,
+    /// What to do when the output is not a terminal, see
+    /// `ProgressBar::set_non_tty`.
+    pub non_tty: Option<NonTtyBehavior>,
+}
+
+impl Default for ProgressStyle {
+    /// The style of a new progress bar, keeping its colors, refresh rate
+    /// and non-TTY behavior.
+    fn default() -> ProgressStyle {
+        ProgressStyle {
+            format: FORMAT.to_owned(),
+            ascii_format: FORMAT.to_owned(),
+            tick_format: TICK_FORMAT.to_owned(),
+            ascii_tick_format: TICK_FORMAT.to_owned(),
+            buffered_glyph: BUFFERED_GLYPH.to_owned(),
+            layout: DEFAULT_LAYOUT.to_owned(),
+            units: Units::Default,
+            show_bar: true,
+            show_speed: true,
+            show_percent: true,
+            show_counter: true,
+            show_time_left: true,
+            show_tick: false,
+            show_message: true,
+            error_color: None,
+            buffered_color: None,
+            max_refresh_rate: None,
+            non_tty: None,
+        }
+    }
+}
+
+impl ProgressStyle {
+    /// Look up a preset by the name of its function, e.g. "wget".
+    pub fn preset(name: &str) -> Option<ProgressStyle> {
+        match name {
+            "default" => Some(ProgressStyle::default()),
+            "npm" => Some(ProgressStyle::npm()),
+            "pip" => Some(ProgressStyle::pip()),
+            "wget" => Some(ProgressStyle::wget()),
+            "cargo" => Some(ProgressStyle::cargo()),
+            "docker" => Some(ProgressStyle::docker()),
+            _ => None,
+        }
+    }
+
+    /// Check the style for invalid glyphs or layout.
+    pub fn validate(&self) -> Result<(), BuildError> {
+        if self.format.chars().count() != 5 {
+            return Err(BuildError::Format(self.format.clone()));
+        }
+        if self.tick_format.is_empty() {
+            return Err(BuildError::TickFormat);
+        }
+        if self.ascii_format.chars().count() != 5 || !self.ascii_format.is_ascii() {
+            return Err(BuildError::Ascii(self.ascii_format.clone()));
+        }
+        if self.ascii_tick_format.is_empty() || !self.ascii_tick_format.is_ascii() {
+            return Err(BuildError::Ascii(self.ascii_tick_format.clone()));
+        }
+        if self.buffered_glyph.chars().count() != 1 {
+            return Err(BuildError::BufferedGlyph(self.buffered_glyph.clone()));
+        }
+        check_layout(&self.layout).map_err(BuildError::Layout)
+    }
+
+    // only shows the given boxes of the default style
+    fn only(layout: &str) -> ProgressStyle {
+        ProgressStyle {
+            layout: layout.to_owned(),
+            show_speed: false,
+            show_percent: false,
+            show_counter: false,
+            show_time_left: false,
+            ..ProgressStyle::default()
+        }
+    }
+
+    /// Like npm: "⸨████░░░░⸩ ⠹ fetchMetadata".
+    pub fn npm() -> ProgressStyle {
+        ProgressStyle {
+            format: "⸨██░⸩".to_owned(),
+            ascii_format: "(##.)".to_owned(),
+            tick_format: "⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏".to_owned(),
+            show_tick: true,
+            ..ProgressStyle::only("{bar} {tick}{message} {paused}")
+        }
+    }
+
+    /// Like pip: "|████████    | 1.20 MB / 3.40 MB 1.00 MB/s 2s".
+    pub fn pip() -> ProgressStyle {
+        ProgressStyle {
+            format: "|██ |".to_owned(),
+            ascii_format: "|## |".to_owned(),
+            units: Units::Bytes,
+            show_counter: true,
+            show_speed: true,
+            show_time_left: true,
+            ..ProgressStyle::only("{message}{bar} {counter}{speed}{time_left}{paused}")
+        }
+    }
+
+    /// Like wget: "file.iso  45.00 % [=======>      ] 1.20 MB/s 2m".
+    pub fn wget() -> ProgressStyle {
+        ProgressStyle {
+            format: "[=> ]".to_owned(),
+            units: Units::Bytes,
+            show_percent: true,
+            show_speed: true,
+            show_time_left: true,
+            ..ProgressStyle::only("{message}{percent}{bar} {speed}{time_left}{paused}")
+        }
+    }
+
+    /// Like cargo: "Building [=======>      ] 42 / 120 ".
+    pub fn cargo() -> ProgressStyle {
+        ProgressStyle {
+            format: "[=> ]".to_owned(),
+            show_counter: true,
+            ..ProgressStyle::only("{message}{bar} {counter}{paused}")
+        }
+    }
+
+    /// Like docker pull: "a3ed95caeb02: Downloading [=====>     ] 12.30 MB / 45.60 MB ".
+    pub fn docker() -> ProgressStyle {
+        ProgressStyle {
+            format: "[=> ]".to_owned(),
+            units: Units::Bytes,
+            show_counter: true,
+            ..ProgressStyle::only("{message}{bar} {counter}{paused}")
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+impl ProgressStyle {
+    /// Parse a style from TOML, fields that are not set keep their default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pbr::ProgressStyle;
+    ///
+    /// let style = ProgressStyle::from_toml(r#"
+    ///     format = "|#-.|"
+    ///     show_speed = false
+    ///     error_color = "magenta"
+    ///     max_refresh_rate_ms = 100
+    ///     non_tty = "hide"
+    /// "#).unwrap();
+    /// assert!(!style.show_speed);
+    /// ```
+    pub fn from_toml(s: &str) -> Result<ProgressStyle, ConfigError> {
+        let style: ProgressStyle = toml::from_str(s).map_err(ConfigError::Parse)?;
+        style.validate().map_err(ConfigError::Invalid)?;
+        Ok(style)
+    }
+
+    /// Load a style from a TOML file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<ProgressStyle, ConfigError> {
+        let path = path.as_ref();
+        let s = fs::read_to_string(path)
+            .map_err(|err| ConfigError::Io(path.display().to_string(), err))?;
+        ProgressStyle::from_toml(&s)
+    }
+
+    /// Load the style set in the `PBR_STYLE` environment variable, `None` if
+    /// it is not set, see `load`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pbr::{ProgressBar, ProgressStyle};
+    ///
+    /// let mut pb = ProgressBar::new(100);
+    /// match ProgressStyle::from_env() {
+    ///     Ok(Some(style)) => pb.set_style(&style),
+    ///     Ok(None) => {}
+    ///     Err(err) => eprintln!("ignoring PBR_STYLE: {}", err),
+    /// }
+    /// ```
+    pub fn from_env() -> Result<Option<ProgressStyle>, ConfigError> {
+        match env::var("PBR_STYLE") {
+            Ok(value) => ProgressStyle::load(&value).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Load a style from the name of a preset (see `preset`), else from a
+    /// TOML file, else from inline TOML.
+    ///
+    /// A value is read as a path if it names an existing file, ends in
+    /// `.toml`, or has a `/` but no `=`. A missing file is reported as
+    /// `ConfigError::Io`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pbr::ProgressStyle;
+    ///
+    /// assert_eq!(ProgressStyle::load("wget").unwrap(), ProgressStyle::wget());
+    /// assert!(!ProgressStyle::load("show_bar = false").unwrap().show_bar);
+    /// ```
+    pub fn load(value: &str) -> Result<ProgressStyle, ConfigError> {
+        let name = value.trim();
+        if let Some(style) = ProgressStyle::preset(name) {
+            Ok(style)
+        } else if Path::new(name).is_file() || name.ends_with(".toml") ||
+                  (name.contains('/') && !name.contains('=')) {
+            ProgressStyle::from_file(name)
+        } else {
+            ProgressStyle::from_toml(value)
+        }
+    }
+}
+
+/// An error loading a `ProgressStyle` from a config.
+#[cfg(feature = "config")]
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read.
+    Io(String, io::Error),
+    /// The TOML is invalid, or has unknown fields or values.
+    Parse(toml::de::Error),
+    /// The style is invalid.
+    Invalid(BuildError),
+}
+
+#[cfg(feature = "config")]
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref path, ref err) => write!(f, "cannot read {}: {}", path, err),
+            ConfigError::Parse(ref err) => write!(f, "invalid style: {}", err),
+            ConfigError::Invalid(ref err) => write!(f, "invalid style: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ConfigError::Io(_, ref err) => Some(err),
+            ConfigError::Parse(ref err) => Some(err),
+            ConfigError::Invalid(ref err) => Some(err),
+        }
+    }
+}
+
+// millis (de)serializes an optional duration as milliseconds.
+#[cfg(feature = "serde")]
+mod millis {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+        d.map(|d| d.as_secs() * 1000 + u64::from(d.subsec_millis())).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<u64>::deserialize(d)?.map(Duration::from_millis))
+    }
+}
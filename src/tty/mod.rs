@@ -10,6 +10,7 @@
 #[derive(Debug)]
 pub struct Width(pub u16);
 #[derive(Debug)]
+#[allow(dead_code)]
 pub struct Height(pub u16);
 
 #[cfg(unix)]
@@ -11,3 +11,11 @@ pub fn terminal_size() -> Option<(Width, Height)> {
 pub fn move_cursor_up(n: usize) -> String {
     format!("{}", termion::cursor::Up(n as u16))
 }
+
+pub fn hide_cursor() -> String {
+    format!("{}", termion::cursor::Hide)
+}
+
+pub fn show_cursor() -> String {
+    format!("{}", termion::cursor::Show)
+}
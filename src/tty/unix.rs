@@ -1,5 +1,6 @@
 extern crate libc;
 use super::{Width, Height};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// Returns the size of the terminal, if available.
 ///
@@ -30,7 +31,7 @@ pub fn terminal_size() -> Option<(Width, Height)> {
         } else {
             0
         };
-        (rows as u16, cols as u16)
+        (rows, cols)
     };
 
     if rows > 0 && cols > 0 {
@@ -45,6 +46,92 @@ pub fn move_cursor_up(n: usize) -> String {
     format!("\x1B[{}A", n)
 }
 
+const SHOW_CURSOR: &str = "\x1B[?25h";
+
+// HIDDEN counts the cursors hidden and not shown again yet.
+static HIDDEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Return string that hides the cursor.
+///
+/// Also makes sure the cursor is shown again on the terminal if the
+/// process gets interrupted by SIGINT or SIGTERM before `show_cursor` is
+/// called, unless the application handles these signals itself.
+pub fn hide_cursor() -> String {
+    if HIDDEN.fetch_add(1, Ordering::SeqCst) == 0 {
+        watch_interrupts(true);
+    }
+    "\x1B[?25l".to_string()
+}
+
+/// Return string that shows the cursor again.
+pub fn show_cursor() -> String {
+    if HIDDEN.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)) == Ok(1) {
+        watch_interrupts(false);
+    }
+    SHOW_CURSOR.to_string()
+}
+
+extern "C" fn on_interrupt(sig: libc::c_int) {
+    use self::libc::{c_char, c_void, close, open, raise, signal, write, O_NOCTTY, O_WRONLY,
+                     SIG_DFL};
+    unsafe {
+        // bars may draw on any handle, so show it on the terminal itself
+        let tty = open(b"/dev/tty\0".as_ptr() as *const c_char, O_WRONLY | O_NOCTTY);
+        if tty >= 0 {
+            write(tty, SHOW_CURSOR.as_ptr() as *const c_void, SHOW_CURSOR.len());
+            close(tty);
+        }
+        signal(sig, SIG_DFL);
+        raise(sig);
+    }
+}
+
+// watch_interrupts installs the signal handlers for the signals left to
+// their default action, or removes them if they are still installed.
+fn watch_interrupts(watch: bool) {
+    use std::{mem, ptr};
+    use self::libc::{sigaction, sigemptyset, sighandler_t, SIGINT, SIGTERM, SIG_DFL};
+    let handler = on_interrupt as extern "C" fn(libc::c_int) as sighandler_t;
+    let (from, to) = if watch { (SIG_DFL, handler) } else { (handler, SIG_DFL) };
+    for &sig in &[SIGINT, SIGTERM] {
+        unsafe {
+            let mut old: sigaction = mem::zeroed();
+            if sigaction(sig, ptr::null(), &mut old) != 0 || old.sa_sigaction != from {
+                continue;
+            }
+            let mut new: sigaction = mem::zeroed();
+            new.sa_sigaction = to;
+            sigemptyset(&mut new.sa_mask);
+            sigaction(sig, &new, ptr::null_mut());
+        }
+    }
+}
+
+#[test]
+/// The handlers of the application are kept as they are
+fn keep_signal_handlers() {
+    use std::{mem, ptr};
+    use self::libc::{sigaction, sighandler_t, siginfo_t, c_int, c_void, SIGTERM, SA_SIGINFO,
+                     SIG_DFL};
+    extern "C" fn on_term(_: c_int, _: *mut siginfo_t, _: *mut c_void) {}
+    let handler = on_term as extern "C" fn(c_int, *mut siginfo_t, *mut c_void) as sighandler_t;
+    unsafe {
+        let mut action: sigaction = mem::zeroed();
+        action.sa_sigaction = handler;
+        action.sa_flags = SA_SIGINFO;
+        sigaction(SIGTERM, &action, ptr::null_mut());
+        hide_cursor();
+        show_cursor();
+        let mut now: sigaction = mem::zeroed();
+        sigaction(SIGTERM, ptr::null(), &mut now);
+        assert_eq!(now.sa_sigaction, handler);
+        assert!(now.sa_flags & SA_SIGINFO != 0);
+        action.sa_sigaction = SIG_DFL;
+        action.sa_flags = 0;
+        sigaction(SIGTERM, &action, ptr::null_mut());
+    }
+}
+
 #[test]
 /// Compare with the output of `stty size`
 fn compare_with_stty() {
@@ -64,8 +151,8 @@ fn compare_with_stty() {
 
     // stdout is "rows cols"
     let mut data = stdout.split_whitespace();
-    let rows = u16::from_str_radix(data.next().unwrap(), 10).unwrap();
-    let cols = u16::from_str_radix(data.next().unwrap(), 10).unwrap();
+    let rows = data.next().unwrap().parse::<u16>().unwrap();
+    let cols = data.next().unwrap().parse::<u16>().unwrap();
     println!("{}", stdout);
     println!("{} {}", rows, cols);
 
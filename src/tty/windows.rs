@@ -34,6 +34,52 @@ pub fn move_cursor_up(n: usize) -> String {
     "".to_string()
 }
 
+/// hide the cursor; return an empty string, just to be aligned with
+/// the unix version.
+///
+/// The cursor is shown again if the console gets interrupted by Ctrl-C
+/// before `show_cursor` is called.
+pub fn hide_cursor() -> String {
+    use self::kernel32::SetConsoleCtrlHandler;
+    use std::sync::Once;
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| unsafe {
+        SetConsoleCtrlHandler(Some(on_interrupt), 1);
+    });
+    set_cursor_visible(false);
+    "".to_string()
+}
+
+/// show the cursor again; return an empty string, just to be aligned
+/// with the unix version.
+pub fn show_cursor() -> String {
+    set_cursor_visible(true);
+    "".to_string()
+}
+
+unsafe extern "system" fn on_interrupt(_: self::winapi::DWORD) -> self::winapi::BOOL {
+    set_cursor_visible(true);
+    // let the next handler (the default one) terminate the process
+    0
+}
+
+fn set_cursor_visible(visible: bool) {
+    use self::kernel32::{GetStdHandle, GetConsoleCursorInfo, SetConsoleCursorInfo};
+    use self::winapi::{CONSOLE_CURSOR_INFO, STD_OUTPUT_HANDLE};
+
+    unsafe {
+        let hand = GetStdHandle(STD_OUTPUT_HANDLE);
+        let mut info = CONSOLE_CURSOR_INFO {
+            dwSize: 0,
+            bVisible: 0,
+        };
+        if GetConsoleCursorInfo(hand, &mut info) != 0 {
+            info.bVisible = if visible { 1 } else { 0 };
+            SetConsoleCursorInfo(hand, &info);
+        }
+    }
+}
+
 fn get_csbi() -> Option<(self::winapi::HANDLE, self::winapi::CONSOLE_SCREEN_BUFFER_INFO)> {
     use self::winapi::HANDLE;
     use self::kernel32::{GetStdHandle, GetConsoleScreenBufferInfo};
@@ -0,0 +1,209 @@
+use pb::{ProgressState, Boxes};
+
+/// A box of the progress bar line, rendered on every draw.
+///
+/// Closures taking the state and the width are widgets too.
+///
+/// # Examples
+///
+/// ```no_run
+/// use pbr::{ProgressBar, ProgressState};
+///
+/// let mut pb = ProgressBar::new(100);
+/// pb.add_suffix_widget(|state: &ProgressState, _width: usize| {
+///     format!(" {:.0}s", state.elapsed.as_secs())
+/// });
+/// ```
+pub trait Widget: Send {
+    /// Render the box for the given state. `width` is the width left for
+    /// the box on the line, boxes are rendered by priority.
+    fn render(&self, state: &ProgressState, width: usize) -> String;
+
+    /// The priority of the box when the line does not fit the terminal:
+    /// boxes with a lower priority are shortened or dropped first. The
+    /// built-in boxes range from 10 (speed) to 90 (stage), the default is 50.
+    fn priority(&self) -> u8 {
+        50
+    }
+
+    /// The width the box may be shortened to before it is dropped, by
+    /// default it is never shortened.
+    fn min_width(&self) -> usize {
+        usize::MAX
+    }
+}
+
+impl<F> Widget for F
+    where F: Fn(&ProgressState, usize) -> String + Send
+{
+    fn render(&self, state: &ProgressState, width: usize) -> String {
+        self(state, width)
+    }
+}
+
+/// The built-in boxes of a progress bar, by their name in a layout.
+///
+/// They are drawn like widgets, by priority and with the width left. A
+/// widget registered with `add_widget` under the name of a built-in box
+/// replaces it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    /// `{stage}`: the current stage, "[1/3] name ".
+    Stage,
+    /// `{message}`: the message set with `message()`.
+    Message,
+    /// `{counter}`: the position and total, and the segment tallies.
+    Counter,
+    /// `{counters}`: the secondary counters.
+    Counters,
+    /// `{tick}`: the spinner.
+    Tick,
+    /// `{bar}`: the bar itself, filling the remaining width.
+    Bar,
+    /// `{percent}`: the percentage done.
+    Percent,
+    /// `{speed}`: the current speed.
+    Speed,
+    /// `{time_left}`: the estimated time left.
+    TimeLeft,
+    /// `{paused}`: "paused" while the bar is paused.
+    Paused,
+}
+
+impl Builtin {
+    /// All built-in boxes.
+    pub const ALL: [Builtin; 10] = [Builtin::Stage,
+                                    Builtin::Message,
+                                    Builtin::Counter,
+                                    Builtin::Counters,
+                                    Builtin::Tick,
+                                    Builtin::Bar,
+                                    Builtin::Percent,
+                                    Builtin::Speed,
+                                    Builtin::TimeLeft,
+                                    Builtin::Paused];
+
+    /// The name of the box in a layout.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Builtin::Stage => "stage",
+            Builtin::Message => "message",
+            Builtin::Counter => "counter",
+            Builtin::Counters => "counters",
+            Builtin::Tick => "tick",
+            Builtin::Bar => "bar",
+            Builtin::Percent => "percent",
+            Builtin::Speed => "speed",
+            Builtin::TimeLeft => "time_left",
+            Builtin::Paused => "paused",
+        }
+    }
+
+    /// The priority of the box when the line does not fit the terminal, see
+    /// `Widget::priority`. The speed goes first, then the counters, then the
+    /// message is shortened. The bar is never dropped this way.
+    pub fn priority(&self) -> u8 {
+        match *self {
+            Builtin::Speed => 10,
+            Builtin::Counters => 20,
+            Builtin::Counter => 30,
+            Builtin::Message => 40,
+            Builtin::Tick => 60,
+            Builtin::TimeLeft => 70,
+            Builtin::Percent => 80,
+            Builtin::Paused => 85,
+            Builtin::Stage => 90,
+            Builtin::Bar => u8::MAX,
+        }
+    }
+
+    /// The width the box may be shortened to before it is dropped, see
+    /// `Widget::min_width`. Only the message is shortened.
+    pub fn min_width(&self) -> usize {
+        match *self {
+            Builtin::Message => 0,
+            _ => usize::MAX,
+        }
+    }
+
+    /// Look up a box by its name in a layout.
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        Builtin::ALL.iter().cloned().find(|b| b.name() == name)
+    }
+}
+
+// BuiltinBox draws a built-in box from what the bar prepared for a draw.
+pub(crate) struct BuiltinBox<'a> {
+    pub(crate) builtin: Builtin,
+    pub(crate) boxes: &'a Boxes,
+}
+
+impl<'a> Widget for BuiltinBox<'a> {
+    fn render(&self, _: &ProgressState, width: usize) -> String {
+        self.boxes.render(self.builtin, width)
+    }
+
+    fn priority(&self) -> u8 {
+        self.builtin.priority()
+    }
+
+    fn min_width(&self) -> usize {
+        self.builtin.min_width()
+    }
+}
+
+// DEFAULT_LAYOUT is the order the boxes are drawn in by default.
+pub(crate) const DEFAULT_LAYOUT: &str = "{stage}{message}{counter}{counters}{tick}{bar}{percent}\
+                                         {speed}{time_left}{paused}";
+
+// Slot is a part of a parsed layout.
+pub(crate) enum Slot {
+    Text(String),
+    Builtin(Builtin),
+    // a widget registered with `add_widget`, looked up by name
+    Named(String),
+    Widget(Box<dyn Widget>),
+}
+
+// parse_layout splits a layout like "{message}{bar} done" into its parts.
+// An unclosed "{" is kept as text.
+pub(crate) fn parse_layout(layout: &str) -> Vec<Slot> {
+    let mut slots = Vec::new();
+    let mut rest = layout;
+    while !rest.is_empty() {
+        let (text, name) = match rest.find('{') {
+            Some(open) => {
+                match rest[open..].find('}') {
+                    Some(close) => (&rest[..open], Some(&rest[open + 1..open + close])),
+                    None => (rest, None),
+                }
+            }
+            None => (rest, None),
+        };
+        if !text.is_empty() {
+            slots.push(Slot::Text(text.to_owned()));
+        }
+        rest = &rest[text.len()..];
+        if let Some(name) = name {
+            slots.push(match Builtin::from_name(name) {
+                Some(b) => Slot::Builtin(b),
+                None => Slot::Named(name.to_owned()),
+            });
+            rest = &rest[name.len() + 2..];
+        }
+    }
+    slots
+}
+
+// check_layout finds the first unclosed "{" of a layout, returning the text
+// from there on.
+pub(crate) fn check_layout(layout: &str) -> Result<(), String> {
+    let mut rest = layout;
+    while let Some(open) = rest.find('{') {
+        match rest[open..].find('}') {
+            Some(close) => rest = &rest[open + close + 1..],
+            None => return Err(rest[open..].to_owned()),
+        }
+    }
+    Ok(())
+}
@@ -1,6 +1,8 @@
 extern crate pbr;
 
-use pbr::{ProgressBar, PbIter};
+use pbr::{ProgressBar, PbIter, MultiBar, FinishState, ProgressBarBuilder, BuildError,
+             Capabilities, DropBehavior};
+use std::io::sink;
 use std::time::Duration;
 use std::thread;
 
@@ -107,3 +109,179 @@ fn npm_bar() {
     }
     pb.finish_println("done!");
 }
+
+#[test]
+fn multibar_dropped_bar() {
+    let mut mb = MultiBar::on(sink());
+    let mut p1 = mb.create_bar(10);
+    let p2 = mb.create_bar(10);
+    thread::spawn(move || {
+        p1.inc();
+        p1.finish();
+        drop(p2);
+    });
+    mb.listen();
+    assert_eq!(mb.finish_states(),
+               &[Some(FinishState::Success), Some(FinishState::Abandoned(String::new()))]);
+    let summary = mb.summary();
+    let rows: Vec<&str> = summary.lines().collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[1].starts_with("1 ") && rows[1].ends_with("  done"));
+    assert!(rows[2].ends_with("  abandoned: "));
+}
+
+#[test]
+fn multibar_finish_once() {
+    let mut mb = MultiBar::on(sink());
+    let mut p1 = mb.create_bar(10);
+    let mut p2 = mb.create_bar(10);
+    thread::spawn(move || {
+        p1.finish();
+        p1.finish_with_error("late");
+        p1.abandon("late");
+        p2.finish();
+    });
+    mb.listen();
+    assert_eq!(mb.finish_states(),
+               &[Some(FinishState::Success), Some(FinishState::Success)]);
+}
+
+#[test]
+fn multibar_gone_before_bar() {
+    let mut mb = MultiBar::on(sink());
+    let mut p1 = mb.create_bar(10);
+    drop(mb);
+    p1.inc();
+    drop(p1);
+}
+
+#[test]
+#[cfg(unix)]
+fn multibar_clear_on_finish() {
+    let mut out = Vec::new();
+    {
+        let mut mb = MultiBar::on(&mut out);
+        mb.set_capabilities(Capabilities::default());
+        let mut p1 = mb.create_bar(10);
+        let mut p2 = mb.create_bar(10);
+        p1.leave_on_finish(false);
+        // wide enough to keep the counter, a narrower line drops it to keep
+        // the bar's minimum width
+        p1.set_width(Some(40));
+        p2.set_width(Some(40));
+        p1.finish();
+        p2.message("last");
+        p2.finish();
+        mb.listen();
+    }
+    let out = String::from_utf8(out).unwrap();
+    let last_draw = out.rsplit("\x1B[1A").next().unwrap();
+    assert!(last_draw.starts_with("\r\rlast10 / 10 "),
+            "should only draw the remaining bar");
+    assert_eq!(last_draw.lines().count(), 1, "should not leave ghost lines");
+}
+
+#[test]
+fn builder() {
+    let pb = ProgressBarBuilder::new(10)
+        .format("[#.]")
+        .build_on(sink());
+    assert_eq!(pb.err(), Some(BuildError::Format("[#.]".to_owned())));
+    let pb = ProgressBarBuilder::new(10).layout("{bar} {speed").build_on(sink());
+    assert_eq!(pb.err(), Some(BuildError::Layout("{speed".to_owned())));
+    let pb = ProgressBarBuilder::new(10).ascii_format("[▌▌░]").build_on(sink());
+    assert_eq!(pb.err(), Some(BuildError::Ascii("[▌▌░]".to_owned())));
+    let pb = ProgressBarBuilder::new(10).buffered_glyph("").build_on(sink());
+    assert_eq!(pb.err(), Some(BuildError::BufferedGlyph(String::new())));
+
+    let mut out = Vec::new();
+    {
+        let mut pb = ProgressBarBuilder::new(10)
+            .format("[#>.]")
+            .tick_format("ab")
+            .show_speed(false)
+            .show_percent(false)
+            .show_time_left(false)
+            .width(30)
+            .message("get ")
+            .build_on(&mut out)
+            .unwrap();
+        pb.set_capabilities(Capabilities::default());
+        assert!(!pb.show_tick, "should not show the tick box as a side effect");
+        pb.add(5);
+        pb.finish();
+    }
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("\rget  5 / 10 [#######>.......] "));
+
+    let mut mb = MultiBar::on(sink());
+    assert!(ProgressBarBuilder::new(10).width(0).build_in(&mut mb).is_err());
+    let mut p1 = ProgressBarBuilder::new(10).build_in(&mut mb).unwrap();
+    p1.finish();
+    mb.listen();
+
+    let mut pb = ProgressBarBuilder::new(10).build().unwrap();
+    pb.set_drop_behavior(DropBehavior::Leave);
+    assert_eq!(pb.capabilities(), Capabilities::detect(), "should detect stdout");
+}
+
+#[test]
+#[cfg(feature = "config")]
+fn style_config() {
+    use pbr::{ProgressStyle, ConfigError, Color, NonTtyBehavior};
+
+    let style = ProgressStyle::from_toml("format = \"|#-.|\"\nerror_color = \"blue\"\n\
+                                          max_refresh_rate_ms = 250\nnon_tty = \"hide\"\n\
+                                          buffered_glyph = \"+\"")
+        .unwrap();
+    assert_eq!(style.format, "|#-.|");
+    assert_eq!(style.buffered_glyph, "+");
+    assert_eq!(style.error_color, Some(Color::Blue));
+    assert_eq!(style.max_refresh_rate, Some(Duration::from_millis(250)));
+    assert_eq!(style.non_tty, Some(NonTtyBehavior::Hide));
+    assert!(style.show_speed, "should keep the defaults");
+
+    match ProgressStyle::from_toml("format = \"|#|\"") {
+        Err(ConfigError::Invalid(BuildError::Format(_))) => {}
+        other => panic!("unexpected {:?}", other),
+    }
+    let err = ProgressStyle::from_toml("show_sped = false").unwrap_err();
+    assert!(err.to_string().contains("show_sped"), "should name the unknown field");
+    assert!(matches!(ProgressStyle::from_file("/nonexistent/pbr.toml"), Err(ConfigError::Io(..))));
+
+    assert_eq!(ProgressStyle::load("wget").unwrap(), ProgressStyle::wget());
+    assert!(!ProgressStyle::load("show_bar = false").unwrap().show_bar);
+    let path = std::env::temp_dir().join("pbr-style-config.toml");
+    std::fs::write(&path, "show_tick = true").unwrap();
+    let style = ProgressStyle::load(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert!(style.unwrap().show_tick, "should load an existing file");
+    for path in &["/nonexistent/pbr", "pbr-style-typo.toml"] {
+        match ProgressStyle::load(path) {
+            Err(ConfigError::Io(ref p, _)) if p == path => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+    assert!(ProgressStyle::load("tick_format = \"|/-\"").is_ok(), "should keep inline TOML");
+}
+
+#[test]
+fn multibar_log_lines() {
+    let mut out = Vec::new();
+    {
+        let mut mb = MultiBar::on(&mut out);
+        mb.set_capabilities(Capabilities { redraw: false, ..Capabilities::default() });
+        mb.println("header");
+        let mut p1 = mb.create_bar(10);
+        p1.set_width(Some(20));
+        p1.inc();
+        p1.inc();
+        p1.finish_print("p1 done");
+        mb.listen();
+    }
+    let out = String::from_utf8(out).unwrap();
+    assert!(!out.contains('\r') && !out.contains('\x1B'), "should not move the cursor");
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "header");
+    assert_eq!(lines.last(), Some(&"p1 done"));
+}